@@ -0,0 +1,230 @@
+// metadata of a schema entry from `# @name` comment lines above it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Annotations {
+    // `@desc text`, joined with a space if given more than once
+    pub description: Option<String>,
+    // `@deprecated` or `@deprecated(use=token)`
+    pub deprecated: bool,
+    // dotted token replacing the deprecated one
+    pub replacement: Option<String>,
+    // `@unit=bytes`
+    pub unit: Option<String>,
+    // `@since=5.10`
+    pub since: Option<String>,
+    // `@default=value`, a literal value as in sysctl.conf(5)
+    pub default: Option<String>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        *self == Annotations::default()
+    }
+}
+
+// names of annotations, while any other `@name` such as `@todo` is ignored
+const NAMES: &[&str] = &["desc", "deprecated", "unit", "since", "default"];
+
+// name of an annotation without `@`, such as `unit` of `unit=bytes`
+fn name(annotation: &str) -> &str {
+    let end = annotation
+        .find(|c: char| c.is_whitespace() || c == '=' || c == '(')
+        .unwrap_or(annotation.len());
+    &annotation[..end]
+}
+
+// annotations of a comment line such as `# @unit=bytes @since=5.10`, beginning with a known name
+pub fn comment(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let comment = line
+        .strip_prefix("#")
+        .or_else(|| line.strip_prefix(";"))?
+        .trim_start();
+    let annotation = comment.strip_prefix("@")?;
+    NAMES.contains(&name(annotation)).then_some(comment)
+}
+
+// text after an unknown annotation up to the next `@` after whitespace
+fn skip_unknown(text: &str) -> &str {
+    let mut previous = ' ';
+    for (index, c) in text.char_indices() {
+        if c == '@' && previous.is_whitespace() {
+            return &text[index..];
+        }
+        previous = c;
+    }
+    ""
+}
+
+// value after `=` until whitespace, or within double quotes, and the rest
+fn parse_value<'a>(name: &str, text: &'a str) -> Result<(&'a str, &'a str), String> {
+    let (value, rest) = match text.strip_prefix("\"") {
+        Some(quoted) => match quoted.split_once("\"") {
+            Some((value, rest)) => (value, rest),
+            None => return Err(format!("'@{}' has an unterminated quote", name)),
+        },
+        None => text.split_at(text.find(char::is_whitespace).unwrap_or(text.len())),
+    };
+    if value.is_empty() {
+        return Err(format!("'@{}' has no value", name));
+    }
+    Ok((value, rest))
+}
+
+// parse annotations of a comment into annotations, with the token of `use=token` relative to
+// prefix
+pub fn parse(comment: &str, prefix: &[&str], annotations: &mut Annotations) -> Result<(), String> {
+    let mut text = comment.trim();
+    while !text.is_empty() {
+        let annotation = match text.strip_prefix("@") {
+            Some(annotation) => annotation,
+            None => {
+                return Err(format!(
+                    "'{}' is not an annotation such as `@unit=bytes`",
+                    text
+                ))
+            }
+        };
+        let (name, rest) = annotation.split_at(name(annotation).len());
+        text = match (name, rest.strip_prefix("=")) {
+            // the rest of the line
+            ("desc", _) => {
+                let description = rest.trim();
+                if description.is_empty() {
+                    return Err("'@desc' has no description".to_string());
+                }
+                annotations.description = Some(match &annotations.description {
+                    Some(previous) => format!("{} {}", previous, description),
+                    None => description.to_string(),
+                });
+                ""
+            }
+            ("deprecated", _) => {
+                annotations.deprecated = true;
+                match rest.strip_prefix("(") {
+                    Some(arguments) => {
+                        let (arguments, rest) = match arguments.split_once(")") {
+                            Some(split) => split,
+                            None => return Err("'@deprecated' has no `)`".to_string()),
+                        };
+                        let token = match arguments.trim().strip_prefix("use") {
+                            Some(token) => token.trim_start().strip_prefix("=").unwrap_or(""),
+                            None => "",
+                        }
+                        .trim();
+                        if token.is_empty() {
+                            return Err(format!(
+                                "'@deprecated({})' is not in format `@deprecated(use=token)`",
+                                arguments
+                            ));
+                        }
+                        let path = [prefix, &token.split(".").collect::<Vec<&str>>()].concat();
+                        annotations.replacement = Some(path.join("."));
+                        rest
+                    }
+                    None => rest,
+                }
+            }
+            ("unit" | "since" | "default", Some(value)) => {
+                let (value, rest) = parse_value(name, value)?;
+                let field = match name {
+                    "unit" => &mut annotations.unit,
+                    "since" => &mut annotations.since,
+                    _ => &mut annotations.default,
+                };
+                *field = Some(value.to_string());
+                rest
+            }
+            ("unit" | "since" | "default", None) => {
+                return Err(format!("'@{}' is not in format `@{}=value`", name, name))
+            }
+            _ => skip_unknown(rest),
+        }
+        .trim_start();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotations() {
+        let mut annotations = Annotations::default();
+        parse(
+            "@deprecated(use=limit) @unit=bytes @since=5.10",
+            &["log"],
+            &mut annotations,
+        )
+        .unwrap();
+        parse(
+            "@default=\"64 K\" @desc Maximum size",
+            &[],
+            &mut annotations,
+        )
+        .unwrap();
+        parse("@desc of a log file", &[], &mut annotations).unwrap();
+        assert_eq!(
+            annotations,
+            Annotations {
+                description: Some("Maximum size of a log file".to_string()),
+                deprecated: true,
+                replacement: Some("log.limit".to_string()),
+                unit: Some("bytes".to_string()),
+                since: Some("5.10".to_string()),
+                default: Some("64 K".to_string()),
+            }
+        );
+
+        assert_eq!(comment("  # @desc Size"), Some("@desc Size"));
+        assert_eq!(comment("; @deprecated"), Some("@deprecated"));
+        assert_eq!(comment("# desc"), None);
+        assert_eq!(comment("# @todo"), None);
+        assert_eq!(comment("# @author me"), None);
+        assert_eq!(comment("log.limit => size"), None);
+    }
+
+    #[test]
+    fn unknown_annotations() {
+        let mut annotations = Annotations::default();
+        parse(
+            "@unit=bytes @author=me @todo check the limit @since=5.10",
+            &[],
+            &mut annotations,
+        )
+        .unwrap();
+        assert_eq!(
+            annotations,
+            Annotations {
+                unit: Some("bytes".to_string()),
+                since: Some("5.10".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_annotations() {
+        let mut annotations = Annotations::default();
+        assert_eq!(
+            parse("@unit bytes", &[], &mut annotations),
+            Err("'@unit' is not in format `@unit=value`".to_string())
+        );
+        assert_eq!(
+            parse("@since=", &[], &mut annotations),
+            Err("'@since' has no value".to_string())
+        );
+        assert_eq!(
+            parse("@default=\"1 2", &[], &mut annotations),
+            Err("'@default' has an unterminated quote".to_string())
+        );
+        assert_eq!(
+            parse("@deprecated(new.key)", &[], &mut annotations),
+            Err("'@deprecated(new.key)' is not in format `@deprecated(use=token)`".to_string())
+        );
+        assert_eq!(
+            parse("@unit=bytes bytes", &[], &mut annotations),
+            Err("'bytes' is not an annotation such as `@unit=bytes`".to_string())
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use super::{check_stdin, load_configs, merge_configs, Args, CliError, Command, ConfigOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const COMMAND: Command = Command {
+    name: "apply",
+    help: "Write values of configs merged in order to /proc/sys like `sysctl -p`
+
+Usage: rust-sysctl-loader apply [OPTIONS] <CONFIG|->...
+
+Options:
+      --root <DIR>  Directory to write values to [default: /proc/sys]
+      --dry-run     Print values and files without writing",
+    flags: &["--dry-run"],
+    value_flags: &["--root"],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+// file of a token under root, failing if the token is not made of file names or the file is
+// outside of root through symlinks
+fn resolve(root: &Path, path: &[&str]) -> Result<PathBuf, String> {
+    let token = path.join(".");
+    if let Some(component) = path.iter().find(|&&component| {
+        component.is_empty()
+            || component == "."
+            || component == ".."
+            || component.contains(['/', '\\'])
+            || Path::new(component).is_absolute()
+    }) {
+        return Err(format!(
+            "'{}' has '{}' which is not a file name",
+            token, component
+        ));
+    }
+    let file = root.join(path.join("/"));
+    // the file itself or, if it does not exist yet, its directory
+    let resolved = match fs::canonicalize(&file) {
+        Ok(resolved) => resolved,
+        Err(_) => match file.parent().map(fs::canonicalize) {
+            Some(Ok(parent)) => parent.join(path[path.len() - 1]),
+            _ => file.clone(),
+        },
+    };
+    if !resolved.starts_with(root) {
+        return Err(format!(
+            "'{}' resolves to '{}' outside of '{}'",
+            token,
+            resolved.display(),
+            root.display()
+        ));
+    }
+    Ok(file)
+}
+
+fn run(args: &Args) -> Result<(), CliError> {
+    if args.positional.is_empty() {
+        return Err(CliError::Usage(format!("no config\n\n{}", COMMAND.help)));
+    }
+    check_stdin(&args.positional.iter().collect::<Vec<_>>())?;
+    let options = ConfigOptions::from_args(args)?;
+    let sources = load_configs(&args.positional, &options)?;
+    let sysctl_conf = merge_configs(&sources, &options)?;
+    let root = PathBuf::from(args.value("--root").unwrap_or("/proc/sys"));
+    let root = fs::canonicalize(&root).unwrap_or(root);
+
+    // keep writing the rest on failure as `sysctl -p` does
+    let mut errors = vec![];
+    for (path, value) in sysctl_conf.leaves() {
+        let token = path.join(".");
+        let file = match resolve(&root, &path) {
+            Ok(file) => file,
+            Err(err) => {
+                errors.push(format!("cannot write '{}': {}", token, err));
+                continue;
+            }
+        };
+        if args.flag("--dry-run") {
+            println!("{} = {} -> {}", token, value, file.display());
+            continue;
+        }
+        match fs::write(&file, value) {
+            Ok(()) => println!("{} = {}", token, value),
+            Err(err) => errors.push(format!(
+                "cannot write '{}' to '{}': {}",
+                token,
+                file.display(),
+                err
+            )),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(CliError::Io(errors.join("\n")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_within_root() {
+        let dir =
+            std::env::temp_dir().join(format!("rust-sysctl-loader-apply-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("root/net")).unwrap();
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        let root = fs::canonicalize(dir.join("root")).unwrap();
+        assert_eq!(
+            resolve(&root, &["net", "ip_forward"]),
+            Ok(root.join("net/ip_forward"))
+        );
+
+        // `/tmp/rv/pwned = hi` is the token `/tmp/rv/pwned`
+        assert_eq!(
+            resolve(&root, &["/tmp/rv/pwned"]),
+            Err("'/tmp/rv/pwned' has '/tmp/rv/pwned' which is not a file name".to_string())
+        );
+        assert_eq!(
+            resolve(&root, &["net", "..", "x"]),
+            Err("'net....x' has '..' which is not a file name".to_string())
+        );
+        assert_eq!(
+            resolve(&root, &["net", ""]),
+            Err("'net.' has '' which is not a file name".to_string())
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("outside"), root.join("link")).unwrap();
+            let result = resolve(&root, &["link", "pwned"]);
+            assert!(result.unwrap_err().contains("outside of"));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
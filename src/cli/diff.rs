@@ -0,0 +1,53 @@
+use super::{check_stdin, load_configs, merge_configs, Args, CliError, Command, ConfigOptions};
+use std::collections::BTreeMap;
+
+pub const COMMAND: Command = Command {
+    name: "diff",
+    help: "Show differences of values between two configs, exiting with 1 if any
+
+Usage: rust-sysctl-loader diff [OPTIONS] <OLD|-> <NEW|->",
+    flags: &[],
+    value_flags: &[],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    if args.positional.len() != 2 {
+        return Err(CliError::Usage(format!(
+            "two configs are required\n\n{}",
+            COMMAND.help
+        )));
+    }
+    check_stdin(&args.positional.iter().collect::<Vec<_>>())?;
+    let options = ConfigOptions::from_args(args)?;
+    let sources = load_configs(&args.positional, &options)?;
+    let old = merge_configs(&sources[..1], &options)?;
+    let new = merge_configs(&sources[1..], &options)?;
+
+    let mut values = BTreeMap::new();
+    for (path, value) in old.leaves() {
+        values.insert(path.join("."), (Some(value), None));
+    }
+    for (path, value) in new.leaves() {
+        values.entry(path.join(".")).or_insert((None, None)).1 = Some(value);
+    }
+    let mut differs = false;
+    for (token, (old, new)) in values {
+        if old == new {
+            continue;
+        }
+        differs = true;
+        if let Some(old) = old {
+            println!("- {} = {}", token, old);
+        }
+        if let Some(new) = new {
+            println!("+ {} = {}", token, new);
+        }
+    }
+    if differs {
+        return Err(CliError::Failure(String::new()));
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use super::{
+    check_stdin, display_path, load_configs, load_schema, parse_schema, print_diagnostics, Args,
+    CliError, Command, ConfigOptions, DiagnosticFormat, STDIN,
+};
+use rust_sysctl_loader::{
+    format::{canonicalize_sysctl, format_schema, format_sysctl, FormatOptions},
+    sysctl,
+};
+use std::fs;
+
+pub const COMMAND: Command = Command {
+    name: "fmt",
+    help: "Format a config or a schema canonically and print it
+
+Usage: rust-sysctl-loader fmt [OPTIONS] <FILE|->
+
+Options:
+      --schema  Format a schema instead of a config
+      --sort    Sort entries by token within each block of consecutive entries
+      --dedup   Remove an entry if the same entry appears later
+      --canonical <SCHEMA>  Rewrite values of size, duration and normalized types into their canonical form
+      --check   Print nothing and exit with 1 if the file would change
+  -w, --write   Write the formatted file in place",
+    flags: &["--schema", "--sort", "--dedup", "--check", "--write"],
+    value_flags: &["--canonical"],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => {
+            return Err(CliError::Usage(format!(
+                "one file is required\n\n{}",
+                COMMAND.help
+            )))
+        }
+    };
+    if args.flag("--write") && path == STDIN {
+        return Err(CliError::Usage("stdin cannot be written".to_string()));
+    }
+    let canonical = args.value("--canonical");
+    if let Some(schema_file) = canonical {
+        if args.flag("--schema") {
+            return Err(CliError::Usage(
+                "--canonical cannot be used with --schema".to_string(),
+            ));
+        }
+        check_stdin(&[path, &schema_file.to_string()])?;
+    }
+    let options = ConfigOptions::from_args(args)?;
+    let format_options = FormatOptions {
+        sort: args.flag("--sort"),
+        dedup: args.flag("--dedup"),
+    };
+    // never format a file which cannot be parsed
+    let (content, formatted) = if args.flag("--schema") {
+        let mut schema_sources = load_schema(path, &options)?;
+        let warnings = parse_schema(&schema_sources, &options)?.warnings;
+        print_diagnostics(&warnings, DiagnosticFormat::Text);
+        // only the file itself is formatted, not the files included from it
+        let content = schema_sources.files.swap_remove(0).content;
+        let formatted = format_schema(&content, &format_options);
+        (content, formatted)
+    } else {
+        let mut sources = load_configs(&[path.to_string()], &options)?;
+        sysctl::parse_sources_diagnostic(&sources[0], &options.parse).map_err(CliError::Parse)?;
+        // only the file itself is formatted, not the files included from it
+        let content = sources.swap_remove(0).files.swap_remove(0).content;
+        match canonical {
+            Some(schema_file) => {
+                let schema_sources = load_schema(schema_file, &options)?;
+                let schema = parse_schema(&schema_sources, &options)?;
+                print_diagnostics(&schema.warnings, DiagnosticFormat::Text);
+                let canonicalized = canonicalize_sysctl(&content, &options.parse, &schema)
+                    .map_err(|diagnostic| {
+                        CliError::Validation(vec![diagnostic.in_file(display_path(path))])
+                    })?;
+                let formatted = format_sysctl(&canonicalized, &options.parse, &format_options);
+                (content, formatted)
+            }
+            None => {
+                let formatted = format_sysctl(&content, &options.parse, &format_options);
+                (content, formatted)
+            }
+        }
+    };
+
+    if args.flag("--check") {
+        if formatted != content {
+            return Err(CliError::Failure(format!(
+                "'{}' would be reformatted",
+                path
+            )));
+        }
+    } else if args.flag("--write") {
+        if formatted != content {
+            fs::write(path, formatted)
+                .map_err(|err| CliError::Io(format!("cannot write '{}': {}", path, err)))?;
+        }
+    } else {
+        print!("{}", formatted);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include() {
+        let dir =
+            std::env::temp_dir().join(format!("rust-sysctl-loader-fmt-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sysctl.conf").display().to_string();
+        fs::write(&path, "include   net.conf\nendpoint=localhost:3000\n").unwrap();
+        fs::write(dir.join("net.conf"), "net.ipv4.ip_forward = 1\n").unwrap();
+        let args = |flags: &[&str]| Args {
+            positional: vec![path.clone()],
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+            values: vec![],
+        };
+        assert_eq!(
+            run(&args(&["--include", "--check"])),
+            Err(CliError::Failure(format!(
+                "'{}' would be reformatted",
+                path
+            )))
+        );
+        assert_eq!(run(&args(&["--include", "--write"])), Ok(()));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "include net.conf\nendpoint = localhost:3000\n"
+        );
+        assert_eq!(run(&args(&["--include", "--check"])), Ok(()));
+        // included file is never rewritten
+        assert_eq!(
+            fs::read_to_string(dir.join("net.conf")).unwrap(),
+            "net.ipv4.ip_forward = 1\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+use super::{check_stdin, load_configs, merge_configs, Args, CliError, Command, ConfigOptions};
+use rust_sysctl_loader::{format::to_sysctl, hashmap::SysctlParameterValue, json::Json};
+
+pub const COMMAND: Command = Command {
+    name: "get",
+    help: "Print the value or subtree of a token in configs merged in order, exiting with 1 if not found
+
+Usage: rust-sysctl-loader get [OPTIONS] <CONFIG|->... <TOKEN>
+
+Options:
+      --format <FORMAT>  sysctl or json [default: sysctl]
+                         A value is printed as is and a subtree as `token = value` lines in sysctl format",
+    flags: &[],
+    value_flags: &["--format"],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let (token, paths) = match args.positional.split_last() {
+        Some((token, paths)) if !paths.is_empty() => (token, paths),
+        _ => {
+            return Err(CliError::Usage(format!(
+                "no config or token\n\n{}",
+                COMMAND.help
+            )))
+        }
+    };
+    let json = match args.value("--format") {
+        None | Some("sysctl") => false,
+        Some("json") => true,
+        Some(format) => {
+            return Err(CliError::Usage(format!(
+                "'{}' is not a format (must be sysctl or json)",
+                format
+            )))
+        }
+    };
+    check_stdin(&paths.iter().collect::<Vec<_>>())?;
+    let options = ConfigOptions::from_args(args)?;
+    let sources = load_configs(paths, &options)?;
+    let sysctl_conf = merge_configs(&sources, &options)?;
+
+    let path = token.split(".").collect::<Vec<&str>>();
+    match sysctl_conf.get(&path) {
+        Some(value @ (SysctlParameterValue::V(_) | SysctlParameterValue::M(_))) if json => {
+            println!("{}", Json::from(value).pretty())
+        }
+        Some(SysctlParameterValue::V(value)) => println!("{}", value),
+        Some(SysctlParameterValue::M(child)) => print!("{}", to_sysctl(child, &path)),
+        _ => return Err(CliError::Failure(format!("'{}' is not found", token))),
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use super::{
+    check_stdin, load_configs, print_diagnostics, read_file, Args, CliError, Command,
+    ConfigOptions, DiagnosticFormat,
+};
+use rust_sysctl_loader::{
+    diagnostic::{Diagnostic, Severity},
+    lint::{lint_str, LintConfig},
+    sysctl,
+};
+
+pub const COMMAND: Command = Command {
+    name: "lint",
+    help: "Lint configs, exiting with 1 if any warning or error is found
+
+Usage: rust-sysctl-loader lint [OPTIONS] <CONFIG|->...
+
+Options:
+      --config <FILE>    Lint config of `rule = off|info|warning|error` lines
+      --format <FORMAT>  text, json or sarif for diagnostics [default: text]
+
+Rules can also be toggled by `# lint: disable=rule,...`, `# lint: enable=rule,...`
+and `# lint: disable-next-line=rule,...` comments.",
+    flags: &[],
+    value_flags: &["--config"],
+    config_options: true,
+    diagnostic_format: true,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    if args.positional.is_empty() {
+        return Err(CliError::Usage(format!("no config\n\n{}", COMMAND.help)));
+    }
+    check_stdin(&args.positional.iter().collect::<Vec<_>>())?;
+    let options = ConfigOptions::from_args(args)?;
+    let lint_config = match args.value("--config") {
+        Some(path) => LintConfig::parse_str(&read_file(path)?)
+            .map_err(|err| CliError::Usage(format!("{} in '{}'", err, path)))?,
+        None => LintConfig::default(),
+    };
+
+    let format = DiagnosticFormat::from_args(args)?;
+
+    let mut diagnostics = vec![];
+    for sources in load_configs(&args.positional, &options)? {
+        sysctl::parse_sources_diagnostic(&sources, &options.parse).map_err(CliError::Parse)?;
+        // each config and the files included from it
+        for source in &sources.files {
+            let file = source.path.display().to_string();
+            let linted =
+                lint_str(&source.content, &options.parse, &lint_config).map_err(|err| {
+                    CliError::Parse(Box::new(
+                        Diagnostic::error("invalid-directive", err).in_file(&file),
+                    ))
+                })?;
+            diagnostics.extend(
+                linted
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.in_file(&file)),
+            );
+        }
+    }
+    print_diagnostics(&diagnostics, format);
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity >= Severity::Warning)
+    {
+        return Err(CliError::Failure(String::new()));
+    }
+    Ok(())
+}
@@ -0,0 +1,568 @@
+mod apply;
+mod diff;
+mod fmt;
+mod get;
+mod lint;
+mod parse;
+mod schema_doc;
+mod schema_json;
+mod schema_template;
+mod validate;
+
+use rust_sysctl_loader::diagnostic::{self, Diagnostic};
+use rust_sysctl_loader::hashmap::{MergeConflictPolicy, SysctlParameterHashMap};
+use rust_sysctl_loader::interpolate::interpolate_diagnostic;
+use rust_sysctl_loader::schema::{self, SchemaHashMap};
+use rust_sysctl_loader::source::Sources;
+use rust_sysctl_loader::sysctl::{self, ParseOptions, Reader};
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+// path of stdin on the command line
+pub const STDIN: &str = "-";
+
+// diff found differences, get found no token, fmt --check found changes or lint found warnings
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_PARSE: i32 = 3;
+pub const EXIT_VALIDATION: i32 = 4;
+pub const EXIT_IO: i32 = 5;
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    Usage(String),
+    Parse(Box<Diagnostic>),
+    Validation(Vec<Diagnostic>),
+    Io(String),
+    // exits with EXIT_FAILURE, printing the message if not empty
+    Failure(String),
+    // exits with the code, the diagnostics having been printed already
+    Reported(i32),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Parse(_) => EXIT_PARSE,
+            CliError::Validation(_) => EXIT_VALIDATION,
+            CliError::Io(_) => EXIT_IO,
+            CliError::Failure(_) => EXIT_FAILURE,
+            CliError::Reported(code) => *code,
+        }
+    }
+    pub fn message(&self) -> Option<String> {
+        match self {
+            CliError::Usage(message) | CliError::Io(message) => Some(message.clone()),
+            CliError::Parse(diagnostic) => Some(diagnostic.render()),
+            CliError::Validation(diagnostics) => Some(
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.render())
+                    .collect::<Vec<String>>()
+                    .join("\n\n"),
+            ),
+            CliError::Failure(message) if !message.is_empty() => Some(message.clone()),
+            CliError::Failure(_) | CliError::Reported(_) => None,
+        }
+    }
+    // diagnostics to be reported in JSON or SARIF
+    fn diagnostics(&self) -> Option<Vec<Diagnostic>> {
+        match self {
+            CliError::Parse(diagnostic) => Some(vec![diagnostic.as_ref().clone()]),
+            CliError::Validation(diagnostics) => Some(diagnostics.clone()),
+            _ => None,
+        }
+    }
+}
+
+const USAGE: &str = "Usage: rust-sysctl-loader <COMMAND> [OPTIONS]
+
+Commands:
+  parse            Parse configs merged in order and print them as JSON
+  validate         Validate configs merged in order with a schema
+  fmt              Format a config or a schema canonically
+  diff             Show differences between two configs
+  apply            Write values of configs merged in order to /proc/sys
+  get              Print the value or subtree of a token
+  lint             Lint configs with configurable rules
+  schema-doc       Print a Markdown reference of a schema
+  schema-template  Print a sysctl.conf template of a schema
+  schema-json      Print a schema as JSON Schema
+  help             Print help of a command
+
+Run `rust-sysctl-loader help <COMMAND>` for help of each command.
+
+Exit codes:
+  0  success
+  1  diff found differences, get found no token, fmt --check found changes
+     or lint found warnings
+  2  usage error
+  3  parse error
+  4  validation error
+  5  I/O error";
+
+pub const CONFIG_OPTIONS_HELP: &str = "Config options:
+      --on-conflict <POLICY>  overlay, base or error on literal value and submap conflict [default: overlay]
+      --include               Enable `include path` directives
+      --interpolate           Resolve `${token}` from the configs or environment variables
+      --quoting               Enable quoted values with escapes
+      --inline-comments       Enable inline comments after values
+      --line-continuation     Enable joining lines ending in `\\`";
+
+const CONFIG_FLAGS: &[&str] = &[
+    "--include",
+    "--interpolate",
+    "--quoting",
+    "--inline-comments",
+    "--line-continuation",
+];
+const CONFIG_VALUE_FLAGS: &[&str] = &["--on-conflict"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+impl DiagnosticFormat {
+    pub fn from_args(args: &Args) -> Result<DiagnosticFormat, CliError> {
+        match args.value("--format") {
+            None | Some("text") => Ok(DiagnosticFormat::Text),
+            Some("json") => Ok(DiagnosticFormat::Json),
+            Some("sarif") => Ok(DiagnosticFormat::Sarif),
+            Some(format) => Err(CliError::Usage(format!(
+                "'{}' is not a diagnostic format (must be text, json or sarif)",
+                format
+            ))),
+        }
+    }
+}
+
+// print diagnostics to stdout as JSON or SARIF, or to stderr as text
+pub fn print_diagnostics(diagnostics: &[Diagnostic], format: DiagnosticFormat) {
+    match format {
+        DiagnosticFormat::Text => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostic);
+            }
+        }
+        DiagnosticFormat::Json => println!("{}", diagnostic::to_json(diagnostics).pretty()),
+        DiagnosticFormat::Sarif => println!(
+            "{}",
+            diagnostic::to_sarif(
+                diagnostics,
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION"),
+                rust_sysctl_loader::lint::RULES,
+            )
+            .pretty()
+        ),
+    }
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub help: &'static str,
+    // boolean flags and flags with value, without config options
+    pub flags: &'static [&'static str],
+    pub value_flags: &'static [&'static str],
+    pub config_options: bool,
+    // `--format` reports parse and validation errors as JSON or SARIF on stdout
+    pub diagnostic_format: bool,
+    pub run: fn(&Args) -> Result<(), CliError>,
+}
+
+const COMMANDS: &[&Command] = &[
+    &parse::COMMAND,
+    &validate::COMMAND,
+    &fmt::COMMAND,
+    &diff::COMMAND,
+    &apply::COMMAND,
+    &get::COMMAND,
+    &lint::COMMAND,
+    &schema_doc::COMMAND,
+    &schema_template::COMMAND,
+    &schema_json::COMMAND,
+];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    pub flags: Vec<String>,
+    pub values: Vec<(String, String)>,
+}
+
+impl Args {
+    pub fn parse(
+        args: &[String],
+        flags: &[&str],
+        value_flags: &[&str],
+        aliases: &[(&str, &str)],
+    ) -> Result<Args, CliError> {
+        let mut parsed = Args::default();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            // `--` ends options
+            if arg == "--" {
+                parsed.positional.extend(rest.by_ref().cloned());
+                break;
+            }
+            if !arg.starts_with("-") || arg == STDIN {
+                parsed.positional.push(arg.clone());
+                continue;
+            }
+            let (name, inline_value) = match arg.split_once("=") {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let name = aliases
+                .iter()
+                .find(|(alias, _)| *alias == name)
+                .map(|(_, name)| *name)
+                .unwrap_or(name);
+            if flags.contains(&name) && inline_value.is_none() {
+                parsed.flags.push(name.to_string());
+            } else if value_flags.contains(&name) {
+                let value = match inline_value.or_else(|| rest.next().cloned()) {
+                    Some(value) => value,
+                    None => return Err(CliError::Usage(format!("'{}' requires a value", name))),
+                };
+                parsed.values.push((name.to_string(), value));
+            } else {
+                return Err(CliError::Usage(format!("unknown option '{}'", arg)));
+            }
+        }
+        Ok(parsed)
+    }
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+    // the last value of the flag
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(flag, _)| flag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct ConfigOptions {
+    pub parse: ParseOptions,
+    pub schema_parse: schema::ParseOptions,
+    pub policy: MergeConflictPolicy,
+    pub interpolation: bool,
+}
+
+impl ConfigOptions {
+    pub fn from_args(args: &Args) -> Result<ConfigOptions, CliError> {
+        let policy = match args.value("--on-conflict") {
+            None | Some("overlay") => MergeConflictPolicy::Overlay,
+            Some("base") => MergeConflictPolicy::Base,
+            Some("error") => MergeConflictPolicy::Error,
+            Some(policy) => {
+                return Err(CliError::Usage(format!(
+                    "'{}' is not a policy (must be overlay, base or error)",
+                    policy
+                )))
+            }
+        };
+        Ok(ConfigOptions {
+            parse: ParseOptions {
+                include: args.flag("--include"),
+                quoting: args.flag("--quoting"),
+                inline_comments: args.flag("--inline-comments"),
+                line_continuation: args.flag("--line-continuation"),
+                // located diagnostics
+                origins: true,
+                ..Default::default()
+            },
+            schema_parse: schema::ParseOptions {
+                inline_comments: args.flag("--inline-comments"),
+                include: args.flag("--include"),
+                ..Default::default()
+            },
+            policy,
+            interpolation: args.flag("--interpolate"),
+        })
+    }
+}
+
+pub fn read_file(path: &str) -> Result<String, CliError> {
+    if path == STDIN {
+        let mut content = String::new();
+        return match io::stdin().read_to_string(&mut content) {
+            Ok(_) => Ok(content),
+            Err(err) => Err(CliError::Io(format!("cannot read stdin: {}", err))),
+        };
+    }
+    fs::read_to_string(path).map_err(|err| CliError::Io(format!("cannot open '{}': {}", path, err)))
+}
+
+// read a config from stdin line by line, with invalid entries reported once loaded into sources
+pub fn read_config_stdin(options: &ParseOptions) -> Result<String, CliError> {
+    let mut reader = Reader::new(io::stdin().lock(), options.clone());
+    reader.by_ref().for_each(drop);
+    reader
+        .into_content()
+        .map_err(|err| CliError::Io(format!("cannot read stdin: {}", err)))
+}
+
+// stdin can be read only once
+pub fn check_stdin(paths: &[&String]) -> Result<(), CliError> {
+    if paths.iter().filter(|&&path| path == STDIN).count() > 1 {
+        return Err(CliError::Usage(format!(
+            "stdin '{}' can be used only once",
+            STDIN
+        )));
+    }
+    Ok(())
+}
+
+pub fn load_configs(paths: &[String], options: &ConfigOptions) -> Result<Vec<Sources>, CliError> {
+    paths
+        .iter()
+        .map(|path| {
+            match path.as_str() {
+                STDIN => sysctl::load_content(
+                    Path::new(display_path(STDIN)),
+                    read_config_stdin(&options.parse)?,
+                    &options.parse,
+                ),
+                path => sysctl::load(Path::new(path), &options.parse),
+            }
+            .map_err(CliError::Io)
+        })
+        .collect()
+}
+
+// parse loaded configs and merge them in order
+pub fn merge_configs<'a>(
+    sources: &'a [Sources],
+    options: &ConfigOptions,
+) -> Result<SysctlParameterHashMap<'a>, CliError> {
+    let mut sysctl_conf = SysctlParameterHashMap::new();
+    for sources in sources {
+        let overlay =
+            sysctl::parse_sources_diagnostic(sources, &options.parse).map_err(CliError::Parse)?;
+        sysctl_conf
+            .merge(overlay, options.policy)
+            .map_err(|err| CliError::Parse(Box::new(Diagnostic::error("merge-conflict", err))))?;
+    }
+    if options.interpolation {
+        interpolate_diagnostic(&mut sysctl_conf, &env::vars().collect()).map_err(
+            |mut diagnostic| {
+                attach_source(&mut diagnostic, sources);
+                CliError::Parse(diagnostic)
+            },
+        )?;
+    }
+    Ok(sysctl_conf)
+}
+
+// load a schema file and, with --include, all files included from it
+pub fn load_schema(path: &str, options: &ConfigOptions) -> Result<Sources, CliError> {
+    match path {
+        STDIN => schema::load_content(
+            Path::new(display_path(STDIN)),
+            read_file(STDIN)?,
+            &options.schema_parse,
+        ),
+        path => schema::load(Path::new(path), &options.schema_parse),
+    }
+    .map_err(CliError::Io)
+}
+
+pub fn parse_schema<'a>(
+    sources: &'a Sources,
+    options: &ConfigOptions,
+) -> Result<SchemaHashMap<'a>, CliError> {
+    schema::parse_sources_diagnostic(sources, &options.schema_parse).map_err(CliError::Parse)
+}
+
+// source line of a diagnostic located in one of the loaded files
+fn attach_source(diagnostic: &mut Diagnostic, sources: &[Sources]) {
+    let file = match (&diagnostic.file, diagnostic.line) {
+        (Some(file), line) if line > 0 => file,
+        _ => return,
+    };
+    diagnostic.source = sources
+        .iter()
+        .flat_map(|sources| &sources.files)
+        .find(|source| source.path.display().to_string() == *file)
+        .and_then(|source| source.content.lines().nth(diagnostic.line - 1))
+        .map(|line| line.to_string());
+}
+
+// attach source lines to validation errors and note the schema lines declaring their tokens
+pub fn locate_schema_errors(
+    diagnostics: &mut [Diagnostic],
+    sources: &[Sources],
+    schema: &SchemaHashMap,
+    schema_sources: &Sources,
+) {
+    for diagnostic in diagnostics {
+        attach_source(diagnostic, sources);
+        let token = diagnostic.token.clone().unwrap_or_default();
+        let origin = match schema.origin(&token) {
+            Some(origin) => origin,
+            None => continue,
+        };
+        let file = origin.file.clone().unwrap_or_default();
+        let declaration = schema_sources
+            .files
+            .iter()
+            .find(|source| source.path.display().to_string() == file)
+            .and_then(|source| source.content.lines().nth(origin.line - 1))
+            .unwrap_or("")
+            .trim();
+        diagnostic.notes.push(format!(
+            "'{}' is declared at {}:{}: `{}`",
+            token, file, origin.line, declaration
+        ));
+    }
+}
+
+// path shown in diagnostics
+pub fn display_path(path: &str) -> &str {
+    match path {
+        STDIN => "<stdin>",
+        path => path,
+    }
+}
+
+fn help(command: &Command) -> String {
+    let mut help = command.help.to_string();
+    if command.config_options {
+        help.push_str("\n\n");
+        help.push_str(CONFIG_OPTIONS_HELP);
+    }
+    help
+}
+
+fn find_command(name: &str) -> Result<&'static Command, CliError> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name)
+        .copied()
+        .ok_or_else(|| CliError::Usage(format!("unknown command '{}'\n\n{}", name, USAGE)))
+}
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let name = match args.first() {
+        Some(name) => name.as_str(),
+        None => return Err(CliError::Usage(USAGE.to_string())),
+    };
+    match name {
+        "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        "help" => {
+            match args.get(1) {
+                Some(name) => println!("{}", help(find_command(name)?)),
+                None => println!("{}", USAGE),
+            }
+            Ok(())
+        }
+        name => {
+            let command = find_command(name)?;
+            let args = &args[1..];
+            if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+                println!("{}", help(command));
+                return Ok(());
+            }
+            let (mut flags, mut value_flags) =
+                (command.flags.to_vec(), command.value_flags.to_vec());
+            if command.config_options {
+                flags.extend(CONFIG_FLAGS);
+                value_flags.extend(CONFIG_VALUE_FLAGS);
+            }
+            if command.diagnostic_format {
+                value_flags.push("--format");
+            }
+            let args = Args::parse(
+                args,
+                &flags,
+                &value_flags,
+                &[("-s", "--schema"), ("-w", "--write")],
+            )
+            .map_err(|err| match err {
+                CliError::Usage(message) => {
+                    CliError::Usage(format!("{}\n\n{}", message, help(command)))
+                }
+                err => err,
+            })?;
+            if !command.diagnostic_format {
+                return (command.run)(&args);
+            }
+            let format = DiagnosticFormat::from_args(&args)?;
+            match (command.run)(&args) {
+                Err(err) if format != DiagnosticFormat::Text => match err.diagnostics() {
+                    Some(diagnostics) => {
+                        print_diagnostics(&diagnostics, format);
+                        Err(CliError::Reported(err.exit_code()))
+                    }
+                    None => Err(err),
+                },
+                result => result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn args() {
+        let result = Args::parse(
+            &strings(&[
+                "a.conf",
+                "-s",
+                "schema.txt",
+                "--include",
+                "--on-conflict=error",
+                "-",
+                "--",
+                "--b.conf",
+            ]),
+            &["--include"],
+            &["--schema", "--on-conflict"],
+            &[("-s", "--schema")],
+        );
+        assert_eq!(
+            result,
+            Ok(Args {
+                positional: strings(&["a.conf", "-", "--b.conf"]),
+                flags: strings(&["--include"]),
+                values: vec![
+                    ("--schema".to_string(), "schema.txt".to_string()),
+                    ("--on-conflict".to_string(), "error".to_string())
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert_eq!(
+            Args::parse(&strings(&["--unknown"]), &[], &[], &[]),
+            Err(CliError::Usage("unknown option '--unknown'".to_string()))
+        );
+        assert_eq!(
+            Args::parse(&strings(&["--schema"]), &[], &["--schema"], &[]),
+            Err(CliError::Usage("'--schema' requires a value".to_string()))
+        );
+    }
+}
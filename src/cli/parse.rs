@@ -0,0 +1,57 @@
+use super::{
+    check_stdin, load_configs, load_schema, locate_schema_errors, merge_configs, parse_schema,
+    print_diagnostics, Args, CliError, Command, ConfigOptions, DiagnosticFormat,
+};
+use rust_sysctl_loader::{json::Json, typed::convert, validator::validate_diagnostics};
+
+pub const COMMAND: Command = Command {
+    name: "parse",
+    help: "Parse configs merged in order and print them as JSON
+
+Usage: rust-sysctl-loader parse [OPTIONS] <CONFIG|->...
+
+Options:
+  -s, --schema <SCHEMA>  Schema file to validate with and convert values into its types",
+    flags: &[],
+    value_flags: &["--schema"],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    if args.positional.is_empty() {
+        return Err(CliError::Usage(format!("no config\n\n{}", COMMAND.help)));
+    }
+    let schema_file = args
+        .value("--schema")
+        .map(|schema_file| schema_file.to_string());
+    let mut paths = args.positional.iter().collect::<Vec<_>>();
+    paths.extend(&schema_file);
+    check_stdin(&paths)?;
+    let options = ConfigOptions::from_args(args)?;
+    let sources = load_configs(&args.positional, &options)?;
+    let sysctl_conf = merge_configs(&sources, &options)?;
+    let schema_file = match &schema_file {
+        Some(schema_file) => schema_file,
+        None => {
+            println!("{}", Json::from(&sysctl_conf).pretty());
+            return Ok(());
+        }
+    };
+    let schema_sources = load_schema(schema_file, &options)?;
+    let schema = parse_schema(&schema_sources, &options)?;
+    print_diagnostics(&schema.warnings, DiagnosticFormat::Text);
+    match convert(&sysctl_conf, &schema) {
+        Ok(typed) => {
+            println!("{}", Json::from(&typed).pretty());
+            Ok(())
+        }
+        // report all errors as validate does
+        Err(_) => {
+            let mut diagnostics = validate_diagnostics(sysctl_conf, schema.clone());
+            locate_schema_errors(&mut diagnostics, &sources, &schema, &schema_sources);
+            Err(CliError::Validation(diagnostics))
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use super::{
+    load_schema, parse_schema, print_diagnostics, Args, CliError, Command, ConfigOptions,
+    DiagnosticFormat,
+};
+use rust_sysctl_loader::doc::{to_html, to_markdown};
+
+pub const COMMAND: Command = Command {
+    name: "schema-doc",
+    help: "Print a Markdown reference of a schema, grouped by token prefix
+
+Usage: rust-sysctl-loader schema-doc [OPTIONS] <SCHEMA|->
+
+Options:
+      --html             Print an HTML document instead of Markdown
+      --title <TITLE>    Title of the reference [default: Configuration reference]",
+    flags: &["--html"],
+    value_flags: &["--title"],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => {
+            return Err(CliError::Usage(format!(
+                "one schema is required\n\n{}",
+                COMMAND.help
+            )))
+        }
+    };
+    let options = ConfigOptions::from_args(args)?;
+    let schema_sources = load_schema(path, &options)?;
+    let schema = parse_schema(&schema_sources, &options)?;
+    print_diagnostics(&schema.warnings, DiagnosticFormat::Text);
+
+    let title = args.value("--title").unwrap_or("Configuration reference");
+    match args.flag("--html") {
+        true => print!("{}", to_html(&schema, title)),
+        false => print!("{}", to_markdown(&schema, title)),
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use super::{
+    load_schema, parse_schema, print_diagnostics, Args, CliError, Command, ConfigOptions,
+    DiagnosticFormat,
+};
+use rust_sysctl_loader::json_schema::to_json_schema;
+
+pub const COMMAND: Command = Command {
+    name: "schema-json",
+    help: "Print a schema as JSON Schema of the values printed by `parse --schema`
+
+Usage: rust-sysctl-loader schema-json [OPTIONS] <SCHEMA|->",
+    flags: &[],
+    value_flags: &[],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => {
+            return Err(CliError::Usage(format!(
+                "one schema is required\n\n{}",
+                COMMAND.help
+            )))
+        }
+    };
+    let options = ConfigOptions::from_args(args)?;
+    let schema_sources = load_schema(path, &options)?;
+    let schema = parse_schema(&schema_sources, &options)?;
+    print_diagnostics(&schema.warnings, DiagnosticFormat::Text);
+
+    println!("{}", to_json_schema(&schema).pretty());
+    Ok(())
+}
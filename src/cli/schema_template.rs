@@ -0,0 +1,41 @@
+use super::{
+    display_path, load_schema, parse_schema, print_diagnostics, Args, CliError, Command,
+    ConfigOptions, DiagnosticFormat,
+};
+use rust_sysctl_loader::{diagnostic::Diagnostic, doc::to_template};
+
+pub const COMMAND: Command = Command {
+    name: "schema-template",
+    help: "Print a sysctl.conf template of a schema with defaults and placeholders
+
+Usage: rust-sysctl-loader schema-template [OPTIONS] <SCHEMA|->",
+    flags: &[],
+    value_flags: &[],
+    config_options: true,
+    diagnostic_format: false,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => {
+            return Err(CliError::Usage(format!(
+                "one schema is required\n\n{}",
+                COMMAND.help
+            )))
+        }
+    };
+    let options = ConfigOptions::from_args(args)?;
+    let schema_sources = load_schema(path, &options)?;
+    let schema = parse_schema(&schema_sources, &options)?;
+    print_diagnostics(&schema.warnings, DiagnosticFormat::Text);
+
+    let template = to_template(&schema).map_err(|err| {
+        CliError::Validation(vec![
+            Diagnostic::error("constraint-violation", err).in_file(display_path(path))
+        ])
+    })?;
+    print!("{}", template);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use super::{
+    check_stdin, load_configs, load_schema, locate_schema_errors, merge_configs, parse_schema,
+    print_diagnostics, Args, CliError, Command, ConfigOptions, DiagnosticFormat, EXIT_VALIDATION,
+};
+use rust_sysctl_loader::{diagnostic::Severity, validator::validate_diagnostics};
+
+pub const COMMAND: Command = Command {
+    name: "validate",
+    help: "Validate configs merged in order with a schema
+
+Usage: rust-sysctl-loader validate [OPTIONS] --schema <SCHEMA|-> <CONFIG|->...
+
+Options:
+  -s, --schema <SCHEMA>  Schema file
+      --format <FORMAT>  text, json or sarif for diagnostics [default: text]",
+    flags: &[],
+    value_flags: &["--schema"],
+    config_options: true,
+    diagnostic_format: true,
+    run,
+};
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let schema_file = match args.value("--schema") {
+        Some(schema_file) => schema_file.to_string(),
+        None => return Err(CliError::Usage(format!("no schema\n\n{}", COMMAND.help))),
+    };
+    if args.positional.is_empty() {
+        return Err(CliError::Usage(format!("no config\n\n{}", COMMAND.help)));
+    }
+    check_stdin(&[args.positional.iter().collect(), vec![&schema_file]].concat())?;
+    let options = ConfigOptions::from_args(args)?;
+    let sources = load_configs(&args.positional, &options)?;
+    let sysctl_conf = merge_configs(&sources, &options)?;
+    let schema_sources = load_schema(&schema_file, &options)?;
+    let schema = parse_schema(&schema_sources, &options)?;
+
+    // schema warnings first, which are reported but never fail as deprecated tokens
+    let mut diagnostics = schema.warnings.clone();
+    diagnostics.extend(validate_diagnostics(sysctl_conf, schema.clone()));
+    locate_schema_errors(&mut diagnostics, &sources, &schema, &schema_sources);
+    let failed = diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error);
+    let format = DiagnosticFormat::from_args(args)?;
+    if format != DiagnosticFormat::Text {
+        print_diagnostics(&diagnostics, format);
+        return match failed {
+            false => Ok(()),
+            true => Err(CliError::Reported(EXIT_VALIDATION)),
+        };
+    }
+    println!(
+        "Validating '{}' with schema '{}': {}",
+        args.positional.join("', '"),
+        schema_file,
+        if failed { "NG" } else { "OK" }
+    );
+    if failed {
+        Err(CliError::Validation(diagnostics))
+    } else {
+        // warnings as validation errors are rendered
+        if let Some(message) = CliError::Validation(diagnostics).message() {
+            if !message.is_empty() {
+                eprintln!("{}", message);
+            }
+        }
+        Ok(())
+    }
+}
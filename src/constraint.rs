@@ -0,0 +1,640 @@
+use crate::{
+    hashmap::{SysctlParameterHashMap, SysctlParameterValue},
+    typed::Value,
+};
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+    fn parse(operator: &str) -> Option<Operator> {
+        match operator {
+            "==" => Some(Operator::Eq),
+            "!=" => Some(Operator::Ne),
+            "<" => Some(Operator::Lt),
+            "<=" => Some(Operator::Le),
+            ">" => Some(Operator::Gt),
+            ">=" => Some(Operator::Ge),
+            _ => None,
+        }
+    }
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering == Ordering::Equal,
+            Operator::Ne => ordering != Ordering::Equal,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::Le => ordering != Ordering::Greater,
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    // dotted token, with a 0-based index of a list item such as `net.ipv4.tcp_rmem[1]`
+    Token(String, Option<usize>),
+    // bool, number or `"string"`
+    Literal(Value),
+}
+
+// chained comparison such as `a <= b <= c`, which holds if every adjacent pair holds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub operands: Vec<Operand>,
+    pub operators: Vec<Operator>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    // token is set
+    Present(String),
+    Compare(Comparison),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    // `condition requires token...`: tokens are set if condition holds
+    Requires(Condition, Vec<String>),
+    // `token conflicts token...`: tokens are not set if token is set
+    Conflicts(String, Vec<String>),
+    // `comparison`: holds if all its tokens are set
+    Compare(Comparison),
+}
+
+// `constraint <rule>` line of a schema, evaluated after the type of each token is checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    // rule as written with tokens relative to its block
+    pub expression: String,
+    pub rule: Rule,
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+// words and operators of an expression, with `"string"` kept quoted
+fn lex(expression: &str) -> Result<Vec<&str>, String> {
+    let mut words = vec![];
+    let mut rest = expression.trim_start();
+    while !rest.is_empty() {
+        let end = if let Some(quoted) = rest.strip_prefix("\"") {
+            match quoted.find("\"") {
+                Some(end) => end + 2,
+                None => return Err(format!("'{}' has an unclosed quote", expression)),
+            }
+        } else if rest.starts_with(['<', '>', '=', '!']) {
+            if rest[1..].starts_with("=") {
+                2
+            } else {
+                1
+            }
+        } else {
+            rest.find(|c: char| c.is_whitespace() || "<>=!\"".contains(c))
+                .unwrap_or(rest.len())
+        };
+        words.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Ok(words)
+}
+
+fn parse_token(word: &str, prefix: &[&str], expression: &str) -> Result<String, String> {
+    if word.is_empty()
+        || word.starts_with(".")
+        || word.ends_with(".")
+        || word.contains("..")
+        || word.contains(['[', ']', '"'])
+        || Operator::parse(word).is_some()
+    {
+        return Err(format!(
+            "'{}' has an invalid token '{}' in constraint",
+            expression, word
+        ));
+    }
+    Ok([prefix, &[word]].concat().join("."))
+}
+
+fn parse_operand(word: &str, prefix: &[&str], expression: &str) -> Result<Operand, String> {
+    if let Some(string) = word
+        .strip_prefix("\"")
+        .and_then(|string| string.strip_suffix("\""))
+    {
+        return Ok(Operand::Literal(Value::String(string.to_string())));
+    }
+    match word {
+        "true" => return Ok(Operand::Literal(Value::Bool(true))),
+        "false" => return Ok(Operand::Literal(Value::Bool(false))),
+        _ => {}
+    }
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        if let Ok(integer) = word.parse::<u64>() {
+            return Ok(Operand::Literal(Value::Integer(integer)));
+        }
+        if let Ok(float) = word.parse::<f64>() {
+            return Ok(Operand::Literal(Value::Float(float)));
+        }
+    }
+    match word.strip_suffix("]").and_then(|word| word.split_once("[")) {
+        Some((token, index)) => match index.parse::<usize>() {
+            Ok(index) => Ok(Operand::Token(
+                parse_token(token, prefix, expression)?,
+                Some(index),
+            )),
+            Err(_) => Err(format!(
+                "'{}' has an invalid index '{}' in constraint",
+                expression, word
+            )),
+        },
+        None => Ok(Operand::Token(parse_token(word, prefix, expression)?, None)),
+    }
+}
+
+fn parse_comparison(
+    words: &[&str],
+    prefix: &[&str],
+    expression: &str,
+) -> Result<Comparison, String> {
+    let mut comparison = Comparison {
+        operands: vec![],
+        operators: vec![],
+    };
+    for (index, word) in words.iter().enumerate() {
+        if index % 2 == 0 {
+            comparison
+                .operands
+                .push(parse_operand(word, prefix, expression)?);
+        } else {
+            match Operator::parse(word) {
+                Some(operator) => comparison.operators.push(operator),
+                None => {
+                    return Err(format!(
+                        "'{}' has '{}' where an operator is expected in constraint",
+                        expression, word
+                    ))
+                }
+            }
+        }
+    }
+    if comparison.operators.is_empty() || comparison.operands.len() == comparison.operators.len() {
+        return Err(format!(
+            "'{}' is not a comparison such as `token < token` in constraint",
+            expression
+        ));
+    }
+    Ok(comparison)
+}
+
+fn parse_tokens(words: &[&str], prefix: &[&str], expression: &str) -> Result<Vec<String>, String> {
+    if words.is_empty() {
+        return Err(format!("'{}' has no token in constraint", expression));
+    }
+    words
+        .iter()
+        .map(|word| parse_token(word, prefix, expression))
+        .collect()
+}
+
+// parse a rule of `constraint <rule>` with tokens relative to prefix
+pub fn parse(expression: &str, prefix: &[&str]) -> Result<Constraint, String> {
+    let expression = expression.trim();
+    let words = lex(expression)?;
+    let rule = if let Some(index) = words.iter().position(|&word| word == "requires") {
+        let condition = match &words[..index] {
+            [token] => Condition::Present(parse_token(token, prefix, expression)?),
+            words => Condition::Compare(parse_comparison(words, prefix, expression)?),
+        };
+        Rule::Requires(
+            condition,
+            parse_tokens(&words[index + 1..], prefix, expression)?,
+        )
+    } else if let Some(index) = words.iter().position(|&word| word == "conflicts") {
+        let token = match &words[..index] {
+            [token] => parse_token(token, prefix, expression)?,
+            _ => {
+                return Err(format!(
+                    "'{}' has no single token before `conflicts` in constraint",
+                    expression
+                ))
+            }
+        };
+        Rule::Conflicts(
+            token,
+            parse_tokens(&words[index + 1..], prefix, expression)?,
+        )
+    } else {
+        Rule::Compare(parse_comparison(&words, prefix, expression)?)
+    };
+    Ok(Constraint {
+        expression: expression.to_string(),
+        rule,
+    })
+}
+
+fn is_set(sysctl_conf: &SysctlParameterHashMap, token: &str) -> bool {
+    matches!(
+        sysctl_conf.get(&token.split(".").collect()),
+        Some(SysctlParameterValue::V(_) | SysctlParameterValue::M(_))
+    )
+}
+
+fn operand_value<'v>(
+    operand: &'v Operand,
+    values: &'v HashMap<String, Value>,
+) -> Option<&'v Value> {
+    match operand {
+        Operand::Token(token, None) => values.get(token),
+        Operand::Token(token, Some(index)) => match values.get(token)? {
+            Value::List(items) => items.get(*index),
+            _ => None,
+        },
+        Operand::Literal(value) => Some(value),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+        (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::Bool(value) => value.to_string(),
+        Value::Integer(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+        Value::String(value) => format!("\"{}\"", value),
+        Value::List(_) => "list".to_string(),
+        Value::Map(_) => "submap".to_string(),
+    }
+}
+
+// first failing pair of a comparison as `a < b` with their values, or None if it holds or any
+// of its tokens has no value
+fn failing_pair(comparison: &Comparison, values: &HashMap<String, Value>) -> Option<String> {
+    let operands = comparison
+        .operands
+        .iter()
+        .map(|operand| operand_value(operand, values))
+        .collect::<Option<Vec<&Value>>>()?;
+    for (index, operator) in comparison.operators.iter().enumerate() {
+        let (a, b) = (operands[index], operands[index + 1]);
+        let holds = match (operator, compare(a, b)) {
+            (Operator::Eq | Operator::Ne, None) => *operator == Operator::Ne,
+            (operator, Some(ordering)) => operator.holds(ordering),
+            (_, None) => false,
+        };
+        if !holds {
+            return Some(format!("{} {} {}", show(a), operator, show(b)));
+        }
+    }
+    None
+}
+
+// first token with an index past the end of its list, as a violation of the comparison
+fn out_of_range(comparison: &Comparison, values: &HashMap<String, Value>) -> Option<String> {
+    comparison
+        .operands
+        .iter()
+        .find_map(|operand| match operand {
+            Operand::Token(token, Some(index)) => match values.get(token)? {
+                Value::List(items) if *index >= items.len() => Some(format!(
+                    "'{}' has no item [{}] in {} items",
+                    token,
+                    index,
+                    items.len()
+                )),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+fn comparison_tokens(comparison: &Comparison) -> Vec<&str> {
+    comparison
+        .operands
+        .iter()
+        .filter_map(|operand| match operand {
+            Operand::Token(token, _) => Some(token.as_str()),
+            Operand::Literal(_) => None,
+        })
+        .collect()
+}
+
+fn first_token(comparison: &Comparison) -> Option<&str> {
+    comparison_tokens(comparison).first().copied()
+}
+
+impl Constraint {
+    // all tokens the rule refers to
+    pub fn tokens(&self) -> Vec<&str> {
+        match &self.rule {
+            Rule::Requires(condition, tokens) => {
+                let mut all = match condition {
+                    Condition::Present(token) => vec![token.as_str()],
+                    Condition::Compare(comparison) => comparison_tokens(comparison),
+                };
+                all.extend(tokens.iter().map(|token| token.as_str()));
+                all
+            }
+            Rule::Conflicts(token, tokens) => [token.as_str()]
+                .into_iter()
+                .chain(tokens.iter().map(|token| token.as_str()))
+                .collect(),
+            Rule::Compare(comparison) => comparison_tokens(comparison),
+        }
+    }
+    // token to locate the violation at and the message, if the config violates the rule, where
+    // values are typed values of the tokens which passed the type check
+    pub fn check(
+        &self,
+        sysctl_conf: &SysctlParameterHashMap,
+        values: &HashMap<String, Value>,
+    ) -> Option<(String, String)> {
+        match &self.rule {
+            Rule::Requires(condition, tokens) => {
+                let located = match condition {
+                    Condition::Present(token) => {
+                        is_set(sysctl_conf, token).then_some(token.as_str())?
+                    }
+                    Condition::Compare(comparison) => {
+                        if let Some(err) = out_of_range(comparison, values) {
+                            let token = first_token(comparison).unwrap_or_default();
+                            return Some((
+                                token.to_string(),
+                                format!("'{}' violates constraint `{}` ({})", token, self, err),
+                            ));
+                        }
+                        // a condition with unknown values never holds
+                        let known = comparison
+                            .operands
+                            .iter()
+                            .all(|operand| operand_value(operand, values).is_some());
+                        if !known || failing_pair(comparison, values).is_some() {
+                            return None;
+                        }
+                        first_token(comparison).unwrap_or_default()
+                    }
+                };
+                let missing = tokens.iter().find(|token| !is_set(sysctl_conf, token))?;
+                Some((
+                    located.to_string(),
+                    format!("'{}' is required by constraint `{}`", missing, self),
+                ))
+            }
+            Rule::Conflicts(token, tokens) => {
+                if !is_set(sysctl_conf, token) {
+                    return None;
+                }
+                let conflicting = tokens.iter().find(|token| is_set(sysctl_conf, token))?;
+                Some((
+                    conflicting.clone(),
+                    format!(
+                        "'{}' conflicts with '{}' by constraint `{}`",
+                        conflicting, token, self
+                    ),
+                ))
+            }
+            Rule::Compare(comparison) => {
+                let token = first_token(comparison).unwrap_or_default();
+                let pair = match out_of_range(comparison, values) {
+                    Some(err) => err,
+                    None => failing_pair(comparison, values)?,
+                };
+                Some((
+                    token.to_string(),
+                    format!("'{}' violates constraint `{}` ({})", token, self, pair),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysctl::parse_str;
+
+    fn values(values: &[(&str, Value)]) -> HashMap<String, Value> {
+        values
+            .iter()
+            .map(|(token, value)| (token.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_rules() {
+        let constraint = parse(
+            "tcp_rmem[0] <= tcp_rmem[1] <= tcp_rmem[2]",
+            &["net", "ipv4"],
+        )
+        .unwrap();
+        assert_eq!(
+            constraint.tokens(),
+            vec![
+                "net.ipv4.tcp_rmem",
+                "net.ipv4.tcp_rmem",
+                "net.ipv4.tcp_rmem"
+            ]
+        );
+        assert_eq!(
+            constraint.rule,
+            Rule::Compare(Comparison {
+                operands: vec![
+                    Operand::Token("net.ipv4.tcp_rmem".to_string(), Some(0)),
+                    Operand::Token("net.ipv4.tcp_rmem".to_string(), Some(1)),
+                    Operand::Token("net.ipv4.tcp_rmem".to_string(), Some(2)),
+                ],
+                operators: vec![Operator::Le, Operator::Le],
+            })
+        );
+        let constraint = parse("log.enabled==true requires log.file log.limit", &[]).unwrap();
+        assert_eq!(
+            constraint.rule,
+            Rule::Requires(
+                Condition::Compare(Comparison {
+                    operands: vec![
+                        Operand::Token("log.enabled".to_string(), None),
+                        Operand::Literal(Value::Bool(true)),
+                    ],
+                    operators: vec![Operator::Eq],
+                }),
+                vec!["log.file".to_string(), "log.limit".to_string()]
+            )
+        );
+        assert_eq!(
+            parse("log.file conflicts log.syslog", &[]).unwrap().rule,
+            Rule::Conflicts("log.file".to_string(), vec!["log.syslog".to_string()])
+        );
+        assert_eq!(
+            parse("log.level != \"debug\"", &[]).unwrap().rule,
+            Rule::Compare(Comparison {
+                operands: vec![
+                    Operand::Token("log.level".to_string(), None),
+                    Operand::Literal(Value::String("debug".to_string())),
+                ],
+                operators: vec![Operator::Ne],
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert_eq!(
+            parse("log.limit", &[]),
+            Err(
+                "'log.limit' is not a comparison such as `token < token` in constraint".to_string()
+            )
+        );
+        assert_eq!(
+            parse("log.limit < ", &[]),
+            Err(
+                "'log.limit <' is not a comparison such as `token < token` in constraint"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse("log.limit 10", &[]),
+            Err("'log.limit 10' has '10' where an operator is expected in constraint".to_string())
+        );
+        assert_eq!(
+            parse("log.file requires", &[]),
+            Err("'log.file requires' has no token in constraint".to_string())
+        );
+        assert_eq!(
+            parse("a b conflicts c", &[]),
+            Err(
+                "'a b conflicts c' has no single token before `conflicts` in constraint"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse("log..limit < 10", &[]),
+            Err("'log..limit < 10' has an invalid token 'log..limit' in constraint".to_string())
+        );
+        assert_eq!(
+            parse("tcp_rmem[x] < 10", &[]),
+            Err("'tcp_rmem[x] < 10' has an invalid index 'tcp_rmem[x]' in constraint".to_string())
+        );
+        assert_eq!(
+            parse("log.level == \"debug", &[]),
+            Err("'log.level == \"debug' has an unclosed quote".to_string())
+        );
+    }
+
+    #[test]
+    fn check() {
+        let sysctl_conf =
+            parse_str("log.enabled = true\nlog.syslog = on\nvm.ratio = 20\nvm.background = 30")
+                .unwrap();
+        let values = values(&[
+            ("log.enabled", Value::Bool(true)),
+            ("vm.ratio", Value::Integer(20)),
+            ("vm.background", Value::Integer(30)),
+            (
+                "tcp_rmem",
+                Value::List(vec![
+                    Value::Integer(4096),
+                    Value::Integer(131072),
+                    Value::Integer(65536),
+                ]),
+            ),
+        ]);
+        let check = |rule: &str| parse(rule, &[]).unwrap().check(&sysctl_conf, &values);
+        assert_eq!(
+            check("log.enabled == true requires log.file"),
+            Some((
+                "log.enabled".to_string(),
+                "'log.file' is required by constraint `log.enabled == true requires log.file`"
+                    .to_string()
+            ))
+        );
+        assert_eq!(check("log.enabled == false requires log.file"), None);
+        assert_eq!(check("log.enabled requires log.syslog"), None);
+        assert_eq!(check("log.missing requires log.file"), None);
+        assert_eq!(
+            check("log.enabled conflicts log.file log.syslog"),
+            Some((
+                "log.syslog".to_string(),
+                "'log.syslog' conflicts with 'log.enabled' by constraint `log.enabled conflicts log.file log.syslog`"
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            check("vm.background < vm.ratio"),
+            Some((
+                "vm.background".to_string(),
+                "'vm.background' violates constraint `vm.background < vm.ratio` (30 < 20)"
+                    .to_string()
+            ))
+        );
+        assert_eq!(check("vm.ratio < vm.background"), None);
+        assert_eq!(check("vm.ratio <= 20.5"), None);
+        assert_eq!(
+            check("tcp_rmem[0] <= tcp_rmem[1] <= tcp_rmem[2]"),
+            Some((
+                "tcp_rmem".to_string(),
+                "'tcp_rmem' violates constraint `tcp_rmem[0] <= tcp_rmem[1] <= tcp_rmem[2]` (131072 <= 65536)"
+                    .to_string()
+            ))
+        );
+        // an index past the end of a list is a violation
+        assert_eq!(
+            check("tcp_rmem[3] < 1"),
+            Some((
+                "tcp_rmem".to_string(),
+                "'tcp_rmem' violates constraint `tcp_rmem[3] < 1` ('tcp_rmem' has no item [3] in 3 items)"
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            check("vm.ratio < tcp_rmem[3] requires log.file"),
+            Some((
+                "vm.ratio".to_string(),
+                "'vm.ratio' violates constraint `vm.ratio < tcp_rmem[3] requires log.file` ('tcp_rmem' has no item [3] in 3 items)"
+                    .to_string()
+            ))
+        );
+        // unknown values are skipped
+        assert_eq!(check("vm.missing < vm.ratio"), None);
+        assert_eq!(check("vm.missing[1] < vm.ratio"), None);
+        assert_eq!(
+            check("log.enabled < 1"),
+            Some((
+                "log.enabled".to_string(),
+                "'log.enabled' violates constraint `log.enabled < 1` (true < 1)".to_string()
+            ))
+        );
+    }
+}
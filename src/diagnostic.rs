@@ -0,0 +1,504 @@
+use crate::json::Json;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn parse(severity: &str) -> Option<Severity> {
+        match severity {
+            "info" => Some(Severity::Info),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    // rule or error code such as `duplicate-token`
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<String>,
+    // 1-based line and column, or 0 if unknown
+    pub line: usize,
+    pub column: usize,
+    // byte length of the span at column, or 0 if unknown
+    pub length: usize,
+    // source line to be shown with a caret under the span
+    pub source: Option<String>,
+    // dotted token the diagnostic is about
+    pub token: Option<String>,
+    // additional context such as `included from path:line`
+    pub notes: Vec<String>,
+}
+
+// kind of an error from parsing a line, which is the code of its diagnostic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    InvalidToken,
+    InvalidValue,
+    InvalidSchemaType,
+    InvalidSyntax,
+}
+
+impl ParseErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::InvalidToken => "invalid-token",
+            ParseErrorKind::InvalidValue => "invalid-value",
+            ParseErrorKind::InvalidSchemaType => "invalid-schema-type",
+            ParseErrorKind::InvalidSyntax => "invalid-syntax",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, message: String) -> ParseError {
+        ParseError { kind, message }
+    }
+}
+
+impl Diagnostic {
+    // error without location
+    pub fn error(code: &str, message: String) -> Diagnostic {
+        Diagnostic {
+            code: code.to_string(),
+            severity: Severity::Error,
+            message,
+            file: None,
+            line: 0,
+            column: 0,
+            length: 0,
+            source: None,
+            token: None,
+            notes: vec![],
+        }
+    }
+
+    // error from parsing the line at index of content, located at its token before separator
+    pub fn parse_error(
+        content: &str,
+        index: usize,
+        separator: &str,
+        err: ParseError,
+    ) -> Diagnostic {
+        let raw = content.lines().nth(index).unwrap_or("");
+        let trimmed = raw.trim_start();
+        let token = match trimmed.split_once(separator) {
+            Some((token, _)) => token.trim(),
+            None => trimmed.strip_prefix("!").unwrap_or("").trim(),
+        };
+        let column = match token {
+            "" => raw.len() - trimmed.len(),
+            token => raw.find(token).unwrap_or(0),
+        } + 1;
+        Diagnostic {
+            line: index + 1,
+            column,
+            length: match token {
+                "" => trimmed.trim_end().len(),
+                token => token.len(),
+            },
+            source: Some(raw.to_string()),
+            token: (!token.is_empty()).then(|| token.to_string()),
+            ..Diagnostic::error(err.kind.code(), err.message)
+        }
+    }
+
+    pub fn in_file(mut self, file: &str) -> Diagnostic {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    fn location(&self) -> Option<String> {
+        match (&self.file, self.line) {
+            (Some(file), 0) => Some(file.clone()),
+            (Some(file), line) => Some(format!("{}:{}:{}", file, line, self.column)),
+            (None, 0) => None,
+            (None, line) => Some(format!("{}:{}", line, self.column)),
+        }
+    }
+
+    // rustc-style with the source line and a caret under the span
+    pub fn render(&self) -> String {
+        let mut rendered = format!("{}[{}]: {}", self.severity, self.code, self.message);
+        let width = self.line.to_string().len();
+        let gutter = " ".repeat(width + 1);
+        if let Some(location) = self.location() {
+            rendered.push_str(&format!("\n{}--> {}", " ".repeat(width), location));
+        }
+        if let (Some(source), true) = (&self.source, self.line > 0) {
+            let source = source.trim_end();
+            // keep tabs before the span to align the caret
+            let padding = source
+                .get(..self.column.saturating_sub(1))
+                .unwrap_or(source)
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            rendered.push_str(&format!("\n{}|", gutter));
+            rendered.push_str(&format!("\n{} | {}", self.line, source));
+            rendered.push_str(&format!(
+                "\n{}| {}{}",
+                gutter,
+                padding,
+                "^".repeat(self.length.max(1))
+            ));
+        }
+        for note in &self.notes {
+            rendered.push_str(&format!("\n{}= note: {}", gutter, note));
+        }
+        rendered
+    }
+
+    pub fn to_json(&self) -> Json {
+        let optional = |value: &Option<String>| match value {
+            Some(value) => Json::string(value.as_str()),
+            None => Json::Null,
+        };
+        let position = |value: usize| match value {
+            0 => Json::Null,
+            value => Json::Integer(value as i64),
+        };
+        Json::object(vec![
+            ("file", optional(&self.file)),
+            ("line", position(self.line)),
+            ("column", position(self.column)),
+            (
+                "end_column",
+                match (self.column, self.length) {
+                    (0, _) | (_, 0) => Json::Null,
+                    (column, length) => Json::Integer((column + length) as i64),
+                },
+            ),
+            ("code", Json::string(self.code.as_str())),
+            ("severity", Json::string(self.severity.to_string())),
+            ("message", Json::string(self.message.as_str())),
+            ("token", optional(&self.token)),
+            (
+                "notes",
+                Json::Array(
+                    self.notes
+                        .iter()
+                        .map(|note| Json::string(note.as_str()))
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+// array of diagnostics
+pub fn to_json(diagnostics: &[Diagnostic]) -> Json {
+    Json::Array(diagnostics.iter().map(Diagnostic::to_json).collect())
+}
+
+// SARIF 2.1.0 log of a single run, with rules of (ID, default severity, description)
+// UTF-16 code units before a byte offset within the source line, as SARIF counts columns
+fn utf16_offset(source: Option<&str>, offset: usize) -> usize {
+    match source.and_then(|source| source.get(..offset)) {
+        Some(before) => before.encode_utf16().count(),
+        None => offset,
+    }
+}
+
+pub fn to_sarif(
+    diagnostics: &[Diagnostic],
+    tool: &str,
+    version: &str,
+    rules: &[(&str, Severity, &str)],
+) -> Json {
+    let level = |severity: Severity| match severity {
+        Severity::Info => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    let rules = rules
+        .iter()
+        .map(|&(id, severity, description)| {
+            Json::object(vec![
+                ("id", Json::string(id)),
+                (
+                    "shortDescription",
+                    Json::object(vec![("text", Json::string(description))]),
+                ),
+                (
+                    "defaultConfiguration",
+                    Json::object(vec![("level", Json::string(level(severity)))]),
+                ),
+            ])
+        })
+        .collect();
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut physical_location = vec![];
+            if let Some(file) = &diagnostic.file {
+                physical_location.push((
+                    "artifactLocation",
+                    Json::object(vec![("uri", Json::string(file.as_str()))]),
+                ));
+            }
+            if diagnostic.line > 0 {
+                let source = diagnostic.source.as_deref();
+                let start = diagnostic.column.max(1) - 1;
+                let mut region = vec![
+                    ("startLine", Json::Integer(diagnostic.line as i64)),
+                    (
+                        "startColumn",
+                        Json::Integer(utf16_offset(source, start) as i64 + 1),
+                    ),
+                ];
+                if diagnostic.length > 0 {
+                    let end = utf16_offset(source, start + diagnostic.length);
+                    region.push(("endColumn", Json::Integer(end as i64 + 1)));
+                }
+                physical_location.push(("region", Json::object(region)));
+            }
+            let mut location = vec![];
+            if !physical_location.is_empty() {
+                location.push(("physicalLocation", Json::object(physical_location)));
+            }
+            if let Some(token) = &diagnostic.token {
+                location.push((
+                    "logicalLocations",
+                    Json::Array(vec![Json::object(vec![(
+                        "fullyQualifiedName",
+                        Json::string(token.as_str()),
+                    )])]),
+                ));
+            }
+            let mut message = diagnostic.message.clone();
+            for note in &diagnostic.notes {
+                message.push_str(&format!(" ({})", note));
+            }
+            let mut result = vec![
+                ("ruleId", Json::string(diagnostic.code.as_str())),
+                ("level", Json::string(level(diagnostic.severity))),
+                (
+                    "message",
+                    Json::object(vec![("text", Json::string(message))]),
+                ),
+            ];
+            if !location.is_empty() {
+                result.push(("locations", Json::Array(vec![Json::object(location)])));
+            }
+            Json::object(result)
+        })
+        .collect();
+    Json::object(vec![
+        ("version", Json::string("2.1.0")),
+        (
+            "$schema",
+            Json::string("https://json.schemastore.org/sarif-2.1.0.json"),
+        ),
+        (
+            "runs",
+            Json::Array(vec![Json::object(vec![
+                (
+                    "tool",
+                    Json::object(vec![(
+                        "driver",
+                        Json::object(vec![
+                            ("name", Json::string(tool)),
+                            ("version", Json::string(version)),
+                            ("rules", Json::Array(rules)),
+                        ]),
+                    )]),
+                ),
+                ("columnKind", Json::string("utf16CodeUnits")),
+                ("results", Json::Array(results)),
+            ])]),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error() {
+        let diagnostic = Diagnostic::parse_error(
+            "endpoint = localhost:3000\n  log..limit = 1024",
+            1,
+            "=",
+            ParseError::new(
+                ParseErrorKind::InvalidToken,
+                "Token 'log..limit' has an invalid hierarchical structure".to_string(),
+            ),
+        )
+        .in_file("sysctl.conf");
+        assert_eq!(
+            diagnostic.to_string(),
+            "sysctl.conf:2:3: error[invalid-token]: Token 'log..limit' has an invalid hierarchical structure"
+        );
+        assert_eq!(diagnostic.token, Some("log..limit".to_string()));
+
+        let diagnostic = Diagnostic::parse_error(
+            "!.debug",
+            0,
+            "=",
+            ParseError::new(
+                ParseErrorKind::InvalidToken,
+                "Token '.debug' has an invalid hierarchical structure".to_string(),
+            ),
+        );
+        assert_eq!(diagnostic.token, Some(".debug".to_string()));
+        let diagnostic = Diagnostic::parse_error(
+            "debug",
+            0,
+            "=>",
+            ParseError::new(
+                ParseErrorKind::InvalidSyntax,
+                "'debug' is not in format `token => schema_type`".to_string(),
+            ),
+        );
+        assert_eq!(
+            (diagnostic.code.as_str(), diagnostic.token),
+            ("invalid-syntax", None)
+        );
+    }
+
+    #[test]
+    fn render() {
+        let mut diagnostic = Diagnostic {
+            file: Some("sysctl.conf".to_string()),
+            line: 12,
+            column: 14,
+            length: 3,
+            source: Some("\tlog.limit = abc".to_string()),
+            ..Diagnostic::error(
+                "type-mismatch",
+                "'log.limit' has not a integer value 'abc'".to_string(),
+            )
+        };
+        diagnostic
+            .notes
+            .push("'log.limit' is declared at schema.txt:4: `log.limit => integer`".to_string());
+        assert_eq!(
+            diagnostic.render(),
+            "error[type-mismatch]: 'log.limit' has not a integer value 'abc'
+  --> sysctl.conf:12:14
+   |
+12 | \tlog.limit = abc
+   | \t            ^^^
+   = note: 'log.limit' is declared at schema.txt:4: `log.limit => integer`"
+        );
+        assert_eq!(
+            Diagnostic::error("missing-token", "'debug' is not found".to_string()).render(),
+            "error[missing-token]: 'debug' is not found"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut diagnostic = Diagnostic::error("missing-token", "'debug' is not found".to_string());
+        diagnostic.token = Some("debug".to_string());
+        diagnostic.notes.push("declared in schema".to_string());
+        assert_eq!(
+            to_json(&[diagnostic]).to_string(),
+            r#"[{"file":null,"line":null,"column":null,"end_column":null,"code":"missing-token","severity":"error","message":"'debug' is not found","token":"debug","notes":["declared in schema"]}]"#
+        );
+    }
+
+    #[test]
+    fn sarif() {
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            file: Some("sysctl.conf".to_string()),
+            line: 3,
+            column: 1,
+            length: 8,
+            token: Some("endpoint".to_string()),
+            ..Diagnostic::error(
+                "duplicate-token",
+                "'endpoint' is already set at line 1".to_string(),
+            )
+        };
+        let sarif = to_sarif(
+            &[
+                diagnostic,
+                Diagnostic::error("invalid-reference", "x".to_string()),
+            ],
+            "tool",
+            "1.0.0",
+            &[(
+                "duplicate-token",
+                Severity::Warning,
+                "token is set more than once",
+            )],
+        );
+        let run = match sarif.get("runs") {
+            Some(Json::Array(runs)) => &runs[0],
+            _ => panic!("no runs"),
+        };
+        assert_eq!(
+            run.get("tool").unwrap().to_string(),
+            r#"{"driver":{"name":"tool","version":"1.0.0","rules":[{"id":"duplicate-token","shortDescription":{"text":"token is set more than once"},"defaultConfiguration":{"level":"warning"}}]}}"#
+        );
+        assert_eq!(
+            run.get("results").unwrap().to_string(),
+            r#"[{"ruleId":"duplicate-token","level":"warning","message":{"text":"'endpoint' is already set at line 1"},"locations":[{"physicalLocation":{"artifactLocation":{"uri":"sysctl.conf"},"region":{"startLine":3,"startColumn":1,"endColumn":9}},"logicalLocations":[{"fullyQualifiedName":"endpoint"}]}]},{"ruleId":"invalid-reference","level":"error","message":{"text":"x"}}]"#
+        );
+        assert_eq!(
+            run.get("columnKind").unwrap().to_string(),
+            r#""utf16CodeUnits""#
+        );
+
+        // columns are UTF-16 code units with a source line, not bytes
+        let source = "app.name = \u{e9}t\u{e9} # \u{1f600} x";
+        let diagnostic = Diagnostic {
+            line: 1,
+            column: source.find('x').unwrap() + 1,
+            length: 1,
+            source: Some(source.to_string()),
+            ..Diagnostic::error("invalid-value", "x".to_string())
+        };
+        let sarif = to_sarif(&[diagnostic], "tool", "1.0.0", &[]);
+        let run = match sarif.get("runs") {
+            Some(Json::Array(runs)) => &runs[0],
+            _ => panic!("no runs"),
+        };
+        assert_eq!(
+            run.get("results").unwrap().to_string(),
+            r#"[{"ruleId":"invalid-value","level":"error","message":{"text":"x"},"locations":[{"physicalLocation":{"region":{"startLine":1,"startColumn":21,"endColumn":22}}}]}]"#
+        );
+    }
+}
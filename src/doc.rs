@@ -0,0 +1,607 @@
+use crate::{
+    schema::{SchemaHashMap, SchemaType},
+    typed::violated_constraints,
+};
+use std::collections::BTreeMap;
+
+// a row of the reference table
+struct Row {
+    token: String,
+    schema_type: String,
+    constraints: Vec<String>,
+    default: Option<String>,
+    // description followed by the version and the deprecation
+    description: Vec<String>,
+}
+
+// rows grouped by the prefix of their tokens, with the top level "" first
+fn groups(schema: &SchemaHashMap) -> BTreeMap<String, Vec<Row>> {
+    let mut groups: BTreeMap<String, Vec<Row>> = BTreeMap::new();
+    for (token, schema_type) in schema.entries() {
+        let annotations = schema.annotations(&token).cloned().unwrap_or_default();
+        let prefix = match token.rsplit_once(".") {
+            Some((prefix, _)) => prefix.to_string(),
+            None => String::new(),
+        };
+        let schema_type = match &annotations.unit {
+            Some(unit) => format!("{} ({})", schema_type, unit),
+            None => schema_type.to_string(),
+        };
+        // constraints of the token or of the tokens below it
+        let constraints = schema
+            .constraints
+            .iter()
+            .filter(|constraint| {
+                constraint.tokens().iter().any(|constrained| {
+                    *constrained == token || constrained.starts_with(&format!("{}.", token))
+                })
+            })
+            .map(|constraint| constraint.to_string())
+            .collect();
+        let mut description = vec![];
+        description.extend(annotations.description);
+        if let Some(since) = &annotations.since {
+            description.push(format!("Since {}", since));
+        }
+        match (annotations.deprecated, &annotations.replacement) {
+            (true, Some(replacement)) => {
+                description.push(format!("Deprecated, use {} instead", replacement))
+            }
+            (true, None) => description.push("Deprecated".to_string()),
+            _ => {}
+        }
+        groups.entry(prefix).or_default().push(Row {
+            token,
+            schema_type,
+            constraints,
+            default: annotations.default,
+            description,
+        });
+    }
+    groups
+}
+
+// backslash before each punctuation which could begin emphasis, code, a link, HTML or an entity,
+// or end a table cell
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "\\`*_[]<>|~&".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// code span fenced by more backticks than any run of them in text, where only `|` is escaped
+// for a table cell
+fn code_markdown(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let longest = text
+        .split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    let padding = match text.starts_with('`') || text.ends_with('`') {
+        true => " ",
+        false => "",
+    };
+    format!(
+        "{}{}{}{}{}",
+        fence,
+        padding,
+        text.replace("|", "\\|"),
+        padding,
+        fence
+    )
+}
+
+// Markdown reference of tokens grouped by their prefix, in a table of type, constraints, default
+// and description
+pub fn to_markdown(schema: &SchemaHashMap, title: &str) -> String {
+    let mut markdown = format!("# {}\n", escape_markdown(title));
+    for (prefix, rows) in groups(schema) {
+        markdown.push('\n');
+        if !prefix.is_empty() {
+            markdown.push_str(&format!("## {}\n\n", code_markdown(&prefix)));
+        }
+        markdown.push_str("| Token | Type | Constraints | Default | Description |\n");
+        markdown.push_str("| --- | --- | --- | --- | --- |\n");
+        for row in rows {
+            let constraints = row
+                .constraints
+                .iter()
+                .map(|constraint| code_markdown(constraint))
+                .collect::<Vec<String>>()
+                .join("<br>");
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                code_markdown(&row.token),
+                code_markdown(&row.schema_type),
+                constraints,
+                code_markdown(row.default.as_deref().unwrap_or("")),
+                row.description
+                    .iter()
+                    .map(|line| escape_markdown(line))
+                    .collect::<Vec<String>>()
+                    .join("<br>")
+            ));
+        }
+    }
+    markdown
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+}
+
+fn code_html(text: &str) -> String {
+    match text.is_empty() {
+        true => String::new(),
+        false => format!("<code>{}</code>", escape_html(text)),
+    }
+}
+
+// same as to_markdown, as a standalone HTML document
+pub fn to_html(schema: &SchemaHashMap, title: &str) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+</head>
+<body>
+<h1>{}</h1>
+",
+        escape_html(title),
+        escape_html(title)
+    );
+    for (prefix, rows) in groups(schema) {
+        if !prefix.is_empty() {
+            html.push_str(&format!("<h2>{}</h2>\n", code_html(&prefix)));
+        }
+        html.push_str("<table>\n<thead>\n<tr><th>Token</th><th>Type</th><th>Constraints</th><th>Default</th><th>Description</th></tr>\n</thead>\n<tbody>\n");
+        for row in rows {
+            let constraints = row
+                .constraints
+                .iter()
+                .map(|constraint| code_html(constraint))
+                .collect::<Vec<String>>()
+                .join("<br>");
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                code_html(&row.token),
+                code_html(&row.schema_type),
+                constraints,
+                code_html(row.default.as_deref().unwrap_or("")),
+                row.description
+                    .iter()
+                    .map(|line| escape_html(line))
+                    .collect::<Vec<String>>()
+                    .join("<br>")
+            ));
+        }
+        html.push_str("</tbody>\n</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// value of a required token without a default, which is valid for its type
+fn placeholder(schema_type: &SchemaType) -> &'static str {
+    match schema_type {
+        SchemaType::Bool() => "false",
+        SchemaType::Integer()
+        | SchemaType::Float()
+        | SchemaType::Size()
+        | SchemaType::Duration() => "0",
+        SchemaType::Normalized(normalized) => placeholder(normalized),
+        // empty string or list
+        _ => "",
+    }
+}
+
+// other values of a required token to try if its placeholder violates a constraint
+fn candidates(schema_type: &SchemaType) -> Vec<String> {
+    match schema_type {
+        SchemaType::Bool() => vec!["true".to_string()],
+        SchemaType::Integer()
+        | SchemaType::Float()
+        | SchemaType::Size()
+        | SchemaType::Duration() => (0..31).map(|shift| (1u64 << shift).to_string()).collect(),
+        SchemaType::Normalized(normalized) => candidates(normalized),
+        // lists of ascending items, with as many items as indexes of constraints may need
+        SchemaType::List(item) => {
+            let items = [vec![placeholder(item).to_string()], candidates(item)].concat();
+            (1..=items.len().min(8))
+                .map(|len| items[..len].join(", "))
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+// a token of the template with its type and description in comments above it
+struct TemplateEntry {
+    token: String,
+    comments: Vec<String>,
+    // lines setting the token, or commented out
+    lines: Vec<String>,
+    // a required token set to a placeholder rather than its default
+    placeholder: bool,
+    candidates: Vec<String>,
+}
+
+fn render_template(entries: &[TemplateEntry]) -> String {
+    let mut template = String::new();
+    for entry in entries.iter().filter(|entry| !entry.lines.is_empty()) {
+        if !template.is_empty() {
+            template.push('\n');
+        }
+        for line in entry.comments.iter().chain(&entry.lines) {
+            template.push_str(line);
+            template.push('\n');
+        }
+    }
+    template
+}
+
+// tokens of each constraint violated by the template
+fn violations(entries: &[TemplateEntry], schema: &SchemaHashMap) -> Vec<Vec<String>> {
+    let template = render_template(entries);
+    let sysctl_conf = match crate::sysctl::parse_str(&template) {
+        Ok(sysctl_conf) => sysctl_conf,
+        Err(_) => return vec![],
+    };
+    violated_constraints(&sysctl_conf, schema)
+        .iter()
+        .map(|constraint| {
+            constraint
+                .tokens()
+                .iter()
+                .map(|token| token.to_string())
+                .collect()
+        })
+        .collect()
+}
+
+// sysctl.conf(5) template of all tokens with their defaults, a commented-out placeholder for an
+// optional token or a submap, or a valid placeholder for a required token, each with its type and
+// description in comments above it, where deprecated optional tokens are left out, a required
+// submap with nothing set below it gets an `example` token, and a placeholder violating a
+// constraint is replaced with another value, failing with the token if none satisfies it
+pub fn to_template(schema: &SchemaHashMap) -> Result<String, String> {
+    let mut entries = vec![];
+    // required submaps with the placeholder value of a token below them
+    let mut submaps = vec![];
+    let all = schema.entries();
+    for (token, schema_type) in &all {
+        let token = token.clone();
+        let annotations = schema.annotations(&token).cloned().unwrap_or_default();
+        let optional = matches!(schema_type, SchemaType::Optional(_));
+        let has_children = all
+            .iter()
+            .any(|(child, _)| child.starts_with(&format!("{}.", token)));
+        if annotations.deprecated && optional {
+            continue;
+        }
+        let candidates = match annotations.default {
+            None if !optional => candidates(schema_type.base()),
+            _ => vec![],
+        };
+        let (lines, placeholder) = match (schema_type.base(), &annotations.default) {
+            (SchemaType::Map(item), _) => {
+                if !optional {
+                    submaps.push((token.clone(), placeholder(item)));
+                }
+                (vec![format!("# {}.<token> = <{}>", token, item)], false)
+            }
+            (SchemaType::Object(), _) => {
+                if !optional {
+                    submaps.push((token.clone(), ""));
+                }
+                // children of an object are set instead
+                match has_children {
+                    true => (vec![], false),
+                    false => (vec![format!("# {}.<token> = <value>", token)], false),
+                }
+            }
+            (_, Some(default)) => (vec![format!("{} = {}", token, default)], false),
+            (schema_type, None) if optional => {
+                (vec![format!("# {} = <{}>", token, schema_type)], false)
+            }
+            (schema_type, None) => (
+                vec![format!("{} = {}", token, placeholder(schema_type))
+                    .trim_end()
+                    .to_string()],
+                true,
+            ),
+        };
+        let described = match &annotations.unit {
+            Some(unit) => format!("{} ({})", schema_type, unit),
+            None => schema_type.to_string(),
+        };
+        let mut comments = vec![format!("# {}: {}", token, described)];
+        if let Some(description) = &annotations.description {
+            comments.push(format!("# {}", description));
+        }
+        entries.push(TemplateEntry {
+            token,
+            comments,
+            lines,
+            placeholder,
+            candidates,
+        });
+    }
+
+    for (token, value) in submaps {
+        let below = format!("{}.", token);
+        let is_set = entries.iter().any(|entry| {
+            entry.token.starts_with(&below) && entry.lines.iter().any(|line| !line.starts_with("#"))
+        });
+        if is_set {
+            continue;
+        }
+        let entry = entries
+            .iter_mut()
+            .find(|entry| entry.token == token)
+            .unwrap();
+        entry.lines.push(
+            format!("{}example = {}", below, value)
+                .trim_end()
+                .to_string(),
+        );
+    }
+
+    // fix placeholders one at a time, from the last token of a violated constraint, with the first
+    // value violating fewer constraints
+    loop {
+        let violated = violations(&entries, schema);
+        let tokens = match violated.first() {
+            Some(tokens) => tokens,
+            None => break,
+        };
+        let index = violated.iter().find_map(|tokens| {
+            entries
+                .iter()
+                .rposition(|entry| entry.placeholder && tokens.contains(&entry.token))
+        });
+        // defaults violating a constraint
+        let index = match index {
+            Some(index) => index,
+            None => {
+                return Err(format!(
+                    "'{}' cannot be set to satisfy a constraint of the schema",
+                    tokens.join("', '")
+                ))
+            }
+        };
+        entries[index].placeholder = false;
+        let fixed = std::mem::take(&mut entries[index].candidates)
+            .into_iter()
+            .any(|value| {
+                entries[index].lines = vec![format!("{} = {}", entries[index].token, value)];
+                violations(&entries, schema).len() < violated.len()
+            });
+        if !fixed {
+            return Err(format!(
+                "'{}' has no placeholder value satisfying the constraints of the schema",
+                entries[index].token
+            ));
+        }
+    }
+    Ok(render_template(&entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> SchemaHashMap<'static> {
+        crate::schema::parse_str(
+            "# @desc Endpoint to listen on
+endpoint => string
+log {
+    # @desc Maximum size of a log file
+    # @unit=bytes @since=5.10 @default=64K
+    limit => size
+    # @deprecated(use=limit)
+    size => optional(size)
+    rotate => duration
+    constraint rotate < 86400
+}
+net.ipv4.tcp_rmem => list(integer)
+constraint net.ipv4.tcp_rmem[0] <= net.ipv4.tcp_rmem[1]",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            to_markdown(&schema(), "Reference"),
+            "# Reference
+
+| Token | Type | Constraints | Default | Description |
+| --- | --- | --- | --- | --- |
+| `endpoint` | `string` |  |  | Endpoint to listen on |
+
+## `log`
+
+| Token | Type | Constraints | Default | Description |
+| --- | --- | --- | --- | --- |
+| `log.limit` | `size (bytes)` |  | `64K` | Maximum size of a log file<br>Since 5.10 |
+| `log.rotate` | `duration` | `rotate < 86400` |  |  |
+| `log.size` | `optional(size)` |  |  | Deprecated, use log.limit instead |
+
+## `net.ipv4`
+
+| Token | Type | Constraints | Default | Description |
+| --- | --- | --- | --- | --- |
+| `net.ipv4.tcp_rmem` | `list(integer)` | `net.ipv4.tcp_rmem[0] <= net.ipv4.tcp_rmem[1]` |  |  |
+"
+        );
+    }
+
+    #[test]
+    fn markdown_escape() {
+        let schema = crate::schema::parse_str(
+            "# @desc Use *bold* _x_ <script> [link](https://example.com) a|b &amp; `code`
+# @default=\"a`b|c\"
+app.name => string",
+        )
+        .unwrap();
+        assert_eq!(
+            to_markdown(&schema, "A *reference*"),
+            "# A \\*reference\\*
+
+## `app`
+
+| Token | Type | Constraints | Default | Description |
+| --- | --- | --- | --- | --- |
+| `app.name` | `string` |  | ``a`b\\|c`` | Use \\*bold\\* \\_x\\_ \\<script\\> \\[link\\](https://example.com) a\\|b \\&amp; \\`code\\` |
+"
+        );
+    }
+
+    #[test]
+    fn template() {
+        let schema = crate::schema::parse_str(
+            "# @desc Endpoint to listen on
+endpoint => string
+debug => normalized(bool)
+log {
+    # @desc Maximum size of a log file
+    # @unit=bytes @default=64K
+    limit => size
+    # @deprecated(use=limit)
+    size => optional(size)
+    file => optional(string)
+    levels => list(integer)
+}
+net.conf => map(integer)
+net.conf.lo.mtu => integer
+app => object
+app.name => string",
+        )
+        .unwrap();
+        let template = to_template(&schema).unwrap();
+        assert_eq!(
+            template,
+            "# app.name: string
+app.name =
+
+# debug: normalized(bool)
+debug = false
+
+# endpoint: string
+# Endpoint to listen on
+endpoint =
+
+# log.file: optional(string)
+# log.file = <string>
+
+# log.levels: list(integer)
+log.levels =
+
+# log.limit: size (bytes)
+# Maximum size of a log file
+log.limit = 64K
+
+# net.conf: map(integer)
+# net.conf.<token> = <integer>
+
+# net.conf.lo.mtu: integer
+net.conf.lo.mtu = 0
+"
+        );
+        let sysctl_conf = crate::sysctl::parse_str(&template).unwrap();
+        assert_eq!(crate::validator::validate(sysctl_conf, schema), Ok(()));
+    }
+
+    #[test]
+    fn template_validates() {
+        let schema = crate::schema::parse_str(
+            "app => object
+net.conf => map(integer)
+vm.a => integer
+vm.b => integer
+constraint vm.a < vm.b
+vm.mem => list(integer)
+constraint vm.mem[0] < vm.mem[1] < vm.mem[2]
+log {
+    file => optional(string)
+}
+log => object",
+        )
+        .unwrap();
+        let template = to_template(&schema).unwrap();
+        assert_eq!(
+            template,
+            "# app: object
+# app.<token> = <value>
+app.example =
+
+# log: object
+log.example =
+
+# log.file: optional(string)
+# log.file = <string>
+
+# net.conf: map(integer)
+# net.conf.<token> = <integer>
+net.conf.example = 0
+
+# vm.a: integer
+vm.a = 0
+
+# vm.b: integer
+vm.b = 1
+
+# vm.mem: list(integer)
+vm.mem = 0, 1, 2
+"
+        );
+        let sysctl_conf = crate::sysctl::parse_str(&template).unwrap();
+        assert_eq!(crate::validator::validate(sysctl_conf, schema), Ok(()));
+
+        // no placeholder satisfies the constraint
+        let schema =
+            crate::schema::parse_str("vm.a => integer\nconstraint vm.a > 9999999999").unwrap();
+        assert_eq!(
+            to_template(&schema),
+            Err(
+                "'vm.a' has no placeholder value satisfying the constraints of the schema"
+                    .to_string()
+            )
+        );
+        let schema = crate::schema::parse_str(
+            "# @default=2\nvm.a => integer\n# @default=1\nvm.b => integer\nconstraint vm.a < vm.b",
+        )
+        .unwrap();
+        assert_eq!(
+            to_template(&schema),
+            Err("'vm.a', 'vm.b' cannot be set to satisfy a constraint of the schema".to_string())
+        );
+    }
+
+    #[test]
+    fn html() {
+        let html = to_html(&schema(), "<Reference>");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>&lt;Reference&gt;</title>"));
+        assert!(html.contains("<h2><code>log</code></h2>"));
+        assert!(html.contains(
+            "<tr><td><code>log.rotate</code></td><td><code>duration</code></td><td><code>rotate &lt; 86400</code></td><td></td><td></td></tr>"
+        ));
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+}
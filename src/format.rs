@@ -0,0 +1,589 @@
+use crate::{
+    annotation,
+    diagnostic::Diagnostic,
+    hashmap::SysctlParameterHashMap,
+    schema::SchemaHashMap,
+    source::parse_include,
+    sysctl::{spans, strip_inline_comment, ParseOptions},
+    typed::canonical_value,
+};
+use std::borrow::Cow;
+
+#[derive(Default)]
+pub struct FormatOptions {
+    // sort entries by token within each block of consecutive entries
+    pub sort: bool,
+    // remove an entry if the same entry appears later, which keeps the result of parsing
+    pub dedup: bool,
+}
+
+enum Line {
+    // `token = value` or `token => schema_type`, with the full token in a schema block
+    Entry { token: String, line: String },
+    // comment, blank, directive or any other line, which ends a block
+    Other(String),
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with("#") || line.starts_with(";")
+}
+
+// normalize a line of `token <separator> value`, or keep it trimmed
+fn format_line(line: &str, separator: &str) -> Line {
+    let line = line.trim();
+    if is_comment(line) || line.starts_with("-") || line.starts_with("!") {
+        return Line::Other(line.to_string());
+    }
+    if let Some(target) = parse_include(line) {
+        return Line::Other(format!("include {}", target));
+    }
+    match line.split_once(separator) {
+        Some((token, value)) => {
+            let token = token.trim().to_string();
+            let line = match value.trim() {
+                "" => format!("{} {}", token, separator),
+                value => format!("{} {} {}", token, separator, value),
+            };
+            Line::Entry { token, line }
+        }
+        None => Line::Other(line.to_string()),
+    }
+}
+
+fn format_lines(mut lines: Vec<Line>, options: &FormatOptions) -> String {
+    if options.dedup {
+        let mut seen = std::collections::HashSet::new();
+        let mut deduped = vec![];
+        for line in lines.into_iter().rev() {
+            if let Line::Entry { token, line: entry } = &line {
+                if !seen.insert((token.clone(), entry.clone())) {
+                    continue;
+                }
+            }
+            deduped.push(line);
+        }
+        deduped.reverse();
+        lines = deduped;
+    }
+    if options.sort {
+        let mut start = 0;
+        for end in 0..=lines.len() {
+            if end == lines.len() || matches!(lines[end], Line::Other(_)) {
+                // stable, so the last of the same tokens still wins
+                lines[start..end].sort_by(|a, b| match (a, b) {
+                    (Line::Entry { token: a, .. }, Line::Entry { token: b, .. }) => a.cmp(b),
+                    _ => std::cmp::Ordering::Equal,
+                });
+                start = end + 1;
+            }
+        }
+    }
+    let mut formatted = String::new();
+    for line in lines {
+        match line {
+            Line::Entry { line, .. } | Line::Other(line) => formatted.push_str(&line),
+        }
+        formatted.push('\n');
+    }
+    formatted
+}
+
+// canonical sysctl.conf(5) with a single space around `=` and no trailing whitespace
+pub fn format_sysctl(
+    sysctl_conf: &str,
+    parse_options: &ParseOptions,
+    options: &FormatOptions,
+) -> String {
+    let mut lines = vec![];
+    let mut continued = false;
+    for line in sysctl_conf.lines() {
+        let trimmed = line.trim();
+        let is_continued = parse_options.line_continuation
+            && trimmed.ends_with("\\")
+            && (continued || !is_comment(trimmed));
+        if continued {
+            // continued physical lines are kept as they are, since their whitespace is a part of value
+            lines.push(Line::Other(line.trim_end().to_string()));
+        } else if is_continued {
+            // continued entry ends a block not to be sorted or removed
+            lines.push(match format_line(line, "=") {
+                Line::Entry { line, .. } | Line::Other(line) => Line::Other(line),
+            });
+        } else {
+            lines.push(format_line(line, "="));
+        }
+        continued = is_continued;
+    }
+    format_lines(lines, options)
+}
+
+// canonical schema with a single space around `=>`, blocks indented by 4 spaces and no trailing
+// whitespace
+pub fn format_schema(schema_conf: &str, options: &FormatOptions) -> String {
+    let mut lines = vec![];
+    let mut blocks: Vec<&str> = vec![];
+    // annotation comments with the comment and blank lines after them, which are a part of the
+    // next entry to be sorted together
+    let mut annotations: Vec<String> = vec![];
+    for line in schema_conf.lines() {
+        let trimmed = line.trim();
+        let code = strip_inline_comment(trimmed).trim_end();
+        let comment = &trimmed[code.len()..];
+        let indent = "    ".repeat(blocks.len());
+        let formatted = if code == "}" {
+            blocks.pop();
+            let indent = "    ".repeat(blocks.len());
+            Line::Other(format!("{}}}{}", indent, comment))
+        } else if !is_comment(code) && !code.contains("=>") && code.ends_with("{") {
+            let token = code.strip_suffix("{").unwrap().trim();
+            blocks.push(token);
+            Line::Other(format!("{}{} {{{}", indent, token, comment))
+        } else {
+            match format_line(line, "=>") {
+                Line::Entry { token, line } => Line::Entry {
+                    token: [&blocks[..], &[token.as_str()]].concat().join("."),
+                    line: format!("{}{}", indent, line),
+                },
+                Line::Other(line) if line.is_empty() => Line::Other(line),
+                Line::Other(line) => Line::Other(format!("{}{}", indent, line)),
+            }
+        };
+        match formatted {
+            Line::Other(line)
+                if annotation::comment(&line).is_some()
+                    || (!annotations.is_empty() && (line.is_empty() || is_comment(trimmed))) =>
+            {
+                annotations.push(line)
+            }
+            Line::Entry { token, line } => {
+                annotations.push(line);
+                lines.push(Line::Entry {
+                    token,
+                    line: annotations.join("\n"),
+                });
+                annotations.clear();
+            }
+            other => {
+                lines.extend(annotations.drain(..).map(Line::Other));
+                lines.push(other);
+            }
+        }
+    }
+    lines.extend(annotations.into_iter().map(Line::Other));
+    format_lines(lines, options)
+}
+
+// rewrite values of normalizing schema types such as `size` into their canonical form,
+// keeping everything else as it is, with an invalid value as Diagnostic located at it
+pub fn canonicalize_sysctl(
+    sysctl_conf: &str,
+    options: &ParseOptions,
+    schema: &SchemaHashMap,
+) -> Result<String, Box<Diagnostic>> {
+    let mut canonicalized = String::new();
+    let mut continued = false;
+    for (index, line) in sysctl_conf.split_inclusive("\n").enumerate() {
+        let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+        let line = &line[..line.len() - ending.len()];
+        let trimmed = line.trim();
+        let was_continued = continued;
+        continued = options.line_continuation
+            && trimmed.ends_with("\\")
+            && (was_continued || !is_comment(trimmed));
+        let token = match line.split_once("=") {
+            Some((token, _)) => token.trim(),
+            None => "",
+        };
+        // continued entries, quoted values and lines other than entries are kept
+        let quoted = options.quoting && {
+            let value = line.split_once("=").map_or("", |(_, value)| value.trim());
+            value.starts_with("\"") || value.starts_with("'")
+        };
+        let schema_type = match schema.resolve(token) {
+            Some(schema_type)
+                if !was_continued
+                    && !continued
+                    && !quoted
+                    && !is_comment(trimmed)
+                    && !trimmed.starts_with("-") =>
+            {
+                schema_type
+            }
+            _ => {
+                canonicalized.push_str(line);
+                canonicalized.push_str(ending);
+                continue;
+            }
+        };
+        let (_, value) = spans(line, options);
+        let canonical =
+            canonical_value(token, &line[value.clone()], schema_type).map_err(|err| {
+                Box::new(Diagnostic {
+                    line: index + 1,
+                    column: value.start + 1,
+                    length: value.len(),
+                    source: Some(line.to_string()),
+                    token: Some(token.to_string()),
+                    ..Diagnostic::error("type-mismatch", err)
+                })
+            })?;
+        match canonical {
+            Some(canonical) => {
+                canonicalized.push_str(&line[..value.start]);
+                canonicalized.push_str(&canonical);
+                canonicalized.push_str(&line[value.end..]);
+            }
+            None => canonicalized.push_str(line),
+        }
+        canonicalized.push_str(ending);
+    }
+    Ok(canonicalized)
+}
+
+// a value as is, or double-quoted with escapes if it would not be read back as is, such as with
+// a newline, whitespace around it, or `#` or `;` of an inline comment
+fn quote_value(value: &str) -> Cow<'_, str> {
+    let needs_quoting = value.contains(['\n', '\r', '#', ';'])
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.starts_with(['"', '\''])
+        || value.ends_with('\\');
+    if !needs_quoting {
+        return Cow::Borrowed(value);
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+// sysctl.conf(5) of all literal values sorted by token, with prefix prepended to each token and
+// values quoted if needed, which are read back as is with quoting
+pub fn to_sysctl(parameter: &SysctlParameterHashMap, prefix: &[&str]) -> String {
+    let mut formatted = String::new();
+    for (path, value) in parameter.leaves() {
+        let token = [prefix, &path].concat().join(".");
+        formatted.push_str(&format!("{} = {}\n", token, quote_value(value)));
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sysctl() {
+        let result = format_sysctl(
+            "  endpoint=localhost:3000
+# comment
+
+log.file   =   /var/log/console.log
+log.name =
+include    net.conf
+!debug  ",
+            &ParseOptions::default(),
+            &FormatOptions::default(),
+        );
+        assert_eq!(
+            result,
+            "endpoint = localhost:3000
+# comment
+
+log.file = /var/log/console.log
+log.name =
+include net.conf
+!debug
+"
+        );
+    }
+
+    #[test]
+    fn sysctl_sort_and_dedup() {
+        let options = FormatOptions {
+            sort: true,
+            dedup: true,
+        };
+        let result = format_sysctl(
+            "# endpoint
+endpoint=localhost:3000
+log.name = default.log
+log.file = /var/log/console.log
+log.name = default.log
+debug = true
+debug = false
+
+# last one wins
+log.limit = 1024
+log.limit = 2048
+log.limit = 1024
+!log.limit
+endpoint = localhost:3001
+-debug = true
+-debug = true",
+            &ParseOptions::default(),
+            &options,
+        );
+        assert_eq!(
+            result,
+            "# endpoint
+debug = true
+debug = false
+endpoint = localhost:3000
+log.file = /var/log/console.log
+log.name = default.log
+
+# last one wins
+log.limit = 2048
+log.limit = 1024
+!log.limit
+endpoint = localhost:3001
+-debug = true
+-debug = true
+"
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let result = format_schema(
+            "# schema
+log.limit=>integer
+  endpoint   =>  string   
+debug =>bool
+log.limit => integer",
+            &FormatOptions {
+                sort: true,
+                dedup: true,
+            },
+        );
+        assert_eq!(
+            result,
+            "# schema
+debug => bool
+endpoint => string
+log.limit => integer
+"
+        );
+    }
+
+    #[test]
+    fn schema_blocks() {
+        let result = format_schema(
+            "net.ipv4{
+  conf.default   {  # default
+rp_filter=>integer
+accept_redirects => bool
+      }
+
+  # forwarding
+forwarding=>bool
+rp_filter => integer
+}
+rp_filter => integer",
+            &FormatOptions {
+                sort: true,
+                dedup: true,
+            },
+        );
+        assert_eq!(
+            result,
+            "net.ipv4 {
+    conf.default {  # default
+        accept_redirects => bool
+        rp_filter => integer
+    }
+
+    # forwarding
+    forwarding => bool
+    rp_filter => integer
+}
+rp_filter => integer
+"
+        );
+    }
+
+    #[test]
+    fn schema_annotations() {
+        let options = FormatOptions {
+            sort: true,
+            dedup: false,
+        };
+        assert_eq!(
+            format_schema("# @desc Zed value\nz => string\na => integer", &options),
+            "a => integer\n# @desc Zed value\nz => string\n"
+        );
+        let result = format_schema(
+            "log {
+    # @desc Rotation interval
+    # @unit=seconds
+
+    # seconds
+    rotate=>duration
+    # @default=64K
+    limit => size
+    file => string
+}",
+            &options,
+        );
+        assert_eq!(
+            result,
+            "log {
+    file => string
+    # @default=64K
+    limit => size
+    # @desc Rotation interval
+    # @unit=seconds
+
+    # seconds
+    rotate => duration
+}
+"
+        );
+        let schema = crate::schema::parse_str(&result).unwrap();
+        assert_eq!(
+            schema
+                .annotations("log.limit")
+                .and_then(|a| a.default.clone()),
+            Some("64K".to_string())
+        );
+    }
+
+    #[test]
+    fn sysctl_from_map() {
+        let parameter = crate::sysctl::parse_str(
+            "endpoint = localhost:3000
+log.limit = 1024
+!debug
+log.file = /var/log/console.log",
+        )
+        .unwrap();
+        assert_eq!(
+            to_sysctl(&parameter, &[]),
+            "endpoint = localhost:3000
+log.file = /var/log/console.log
+log.limit = 1024
+"
+        );
+        assert_eq!(
+            to_sysctl(&parameter, &["app"]),
+            "app.endpoint = localhost:3000
+app.log.file = /var/log/console.log
+app.log.limit = 1024
+"
+        );
+    }
+
+    #[test]
+    fn sysctl_from_map_quoted() {
+        let options = ParseOptions {
+            quoting: true,
+            inline_comments: true,
+            line_continuation: true,
+            ..Default::default()
+        };
+        let content = r##"banner = "line1\nline2\t\"quoted\""
+prompt = "  > "
+color = "#ffffff ; white"
+single = "'single'"
+path = "C:\\"
+debug = true;false
+"##;
+        let parameter = crate::sysctl::parse_str_with(content, &options).unwrap();
+        let formatted = to_sysctl(&parameter, &[]);
+        assert_eq!(
+            formatted,
+            r##"banner = "line1\nline2\t\"quoted\""
+color = "#ffffff ; white"
+debug = "true;false"
+path = "C:\\"
+prompt = "  > "
+single = "'single'"
+"##
+        );
+        assert_eq!(
+            crate::sysctl::parse_str_with(&formatted, &options),
+            Ok(parameter)
+        );
+    }
+
+    #[test]
+    fn sysctl_line_continuation() {
+        let options = ParseOptions {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let result = format_sysctl(
+            "kernel.core_pattern=|/usr/lib/systemd/systemd-coredump \\
+  %P=%u \\
+  %g
+debug=true",
+            &options,
+            &FormatOptions::default(),
+        );
+        assert_eq!(
+            result,
+            "kernel.core_pattern = |/usr/lib/systemd/systemd-coredump \\
+  %P=%u \\
+  %g
+debug = true
+"
+        );
+    }
+
+    #[test]
+    fn canonical() {
+        let schema = crate::schema::parse_str(
+            "debug => normalized(bool)
+log.mode => normalized(integer)
+log.limit => size
+log.rotate => duration
+log.levels => list(normalized(integer))
+endpoint => string",
+        )
+        .unwrap();
+        let result = canonicalize_sysctl(
+            "# canonical
+debug=YES
+log.mode = 0755 # mode
+log.limit = 64K
+log.rotate = 5m\r
+log.levels = 0x1f, 010
+endpoint = localhost:3000
+-debug = on",
+            &ParseOptions {
+                inline_comments: true,
+                ..Default::default()
+            },
+            &schema,
+        );
+        assert_eq!(
+            result,
+            Ok("# canonical
+debug=true
+log.mode = 493 # mode
+log.limit = 65536
+log.rotate = 300\r
+log.levels = 31, 8
+endpoint = localhost:3000
+-debug = on"
+                .to_string())
+        );
+
+        let result = canonicalize_sysctl("\ndebug = maybe", &ParseOptions::default(), &schema);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "2:9: error[type-mismatch]: 'debug' has not a bool value 'maybe'"
+        );
+    }
+}
@@ -1,32 +1,86 @@
-use std::{collections::HashMap, fmt};
+use std::{borrow::Cow, collections::HashMap, fmt, ops::Range};
 
 #[derive(PartialEq)]
 pub enum SysctlParameterValue<'a> {
-    V(&'a str),
+    V(Cow<'a, str>),
     M(Box<SysctlParameterHashMap<'a>>),
+    // tombstone deleting an inherited value on merge
+    D,
 }
 impl fmt::Debug for SysctlParameterValue<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self {
             Self::V(value) => write!(f, "\"{}\"", value.replace("\"", "\\\"")),
             Self::M(value) => write!(f, "{:?}", value),
+            Self::D => write!(f, "null"),
         }
     }
 }
 impl<'a> SysctlParameterValue<'a> {
     pub fn from_map(value: HashMap<&'a str, SysctlParameterValue<'a>>) -> Self {
-        SysctlParameterValue::M(Box::new(SysctlParameterHashMap { items: value }))
+        SysctlParameterValue::M(Box::new(SysctlParameterHashMap {
+            items: value,
+            ..Default::default()
+        }))
+    }
+    fn without_tombstones(self) -> Self {
+        match self {
+            SysctlParameterValue::M(child) => {
+                let mut map = SysctlParameterHashMap::new();
+                // merging into an empty map drops all tombstones and never conflicts
+                let _ = map.merge(*child, MergeConflictPolicy::Overlay);
+                SysctlParameterValue::M(Box::new(map))
+            }
+            value => value,
+        }
     }
 }
 
-#[derive(PartialEq)]
+// policy for a token which is a literal value on one side and a submap on the other side of merge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeConflictPolicy {
+    // overlay replaces base
+    Overlay,
+    // base is kept
+    Base,
+    // merge fails
+    Error,
+}
+
+// where a literal value or a tombstone is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub file: Option<String>,
+    // 1-based line
+    pub line: usize,
+    // byte ranges of the token and the value within the line
+    pub key: Range<usize>,
+    pub value: Range<usize>,
+}
+
 pub struct SysctlParameterHashMap<'a> {
     pub items: HashMap<&'a str, SysctlParameterValue<'a>>,
+    // origin of each literal value or tombstone in items, which is not compared
+    pub origins: HashMap<&'a str, Origin>,
+    // set under a tombstone of this submap, which replaces the inherited submap on merge
+    pub cleared: bool,
+}
+impl PartialEq for SysctlParameterHashMap<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+impl Default for SysctlParameterHashMap<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl<'a> SysctlParameterHashMap<'a> {
     pub fn new() -> Self {
         SysctlParameterHashMap {
             items: HashMap::new(),
+            origins: HashMap::new(),
+            cleared: false,
         }
     }
     pub fn get(&self, path: &Vec<&'a str>) -> Option<&SysctlParameterValue<'a>> {
@@ -52,22 +106,75 @@ impl<'a> SysctlParameterHashMap<'a> {
             }
         }
     }
+    // all literal values with their paths, sorted by path
+    pub fn leaves(&self) -> Vec<(Vec<&'a str>, &str)> {
+        let mut leaves = vec![];
+        for (&token, value) in &self.items {
+            match value {
+                SysctlParameterValue::V(value) => leaves.push((vec![token], value.as_ref())),
+                SysctlParameterValue::M(child) => {
+                    for (path, value) in child.leaves() {
+                        leaves.push(([vec![token], path].concat(), value));
+                    }
+                }
+                SysctlParameterValue::D => {}
+            }
+        }
+        leaves.sort();
+        leaves
+    }
+    // origin of the literal value of a dotted token such as `log.limit`, if tracked
+    pub fn origin(&self, token: &str) -> Option<&Origin> {
+        self.origin_at(&token.split(".").collect::<Vec<&str>>())
+    }
+    fn origin_at(&self, path: &[&str]) -> Option<&Origin> {
+        match path {
+            [] => None,
+            [token] => match self.items.get(*token) {
+                Some(SysctlParameterValue::V(_)) => self.origins.get(*token),
+                _ => None,
+            },
+            [token, path @ ..] => match self.items.get(*token) {
+                Some(SysctlParameterValue::M(child)) => child.origin_at(path),
+                _ => None,
+            },
+        }
+    }
     pub fn insert(&mut self, path: &Vec<&'a str>, value: &'a str) {
+        self.insert_value(path, SysctlParameterValue::V(Cow::Borrowed(value)))
+    }
+    pub fn insert_value(&mut self, path: &Vec<&'a str>, value: SysctlParameterValue<'a>) {
+        self.insert_with_origin(path, value, None)
+    }
+    pub fn insert_with_origin(
+        &mut self,
+        path: &Vec<&'a str>,
+        value: SysctlParameterValue<'a>,
+        origin: Option<Origin>,
+    ) {
         match path.len() {
-            0 => return,
+            0 => {}
             1 => {
                 // set map value
-                self.items
-                    .insert(path.first().unwrap(), SysctlParameterValue::V(value));
+                let &token = path.first().unwrap();
+                self.items.insert(token, value);
+                match origin {
+                    Some(origin) => self.origins.insert(token, origin),
+                    None => self.origins.remove(token),
+                };
             }
             _ => {
                 let &child_token = path.first().unwrap();
-                // set initial map
-                if !self.items.contains_key(child_token) {
-                    self.items.insert(
-                        child_token,
-                        SysctlParameterValue::M(Box::new(SysctlParameterHashMap::new())),
-                    );
+                // set initial map, or replace a literal value or a tombstone
+                match self.items.get(child_token) {
+                    Some(SysctlParameterValue::M(_)) => {}
+                    value => {
+                        let mut child = SysctlParameterHashMap::new();
+                        child.cleared = matches!(value, Some(SysctlParameterValue::D));
+                        self.items
+                            .insert(child_token, SysctlParameterValue::M(Box::new(child)));
+                        self.origins.remove(child_token);
+                    }
                 }
                 // update map recursively
                 if let Some(SysctlParameterValue::M(child)) = self.items.get_mut(child_token) {
@@ -76,14 +183,297 @@ impl<'a> SysctlParameterHashMap<'a> {
                         _ => &[],
                     }
                     .to_vec();
-                    child.insert(&path, value);
+                    child.insert_with_origin(&path, value, origin);
                 }
             }
         }
     }
+    // deep merge overlay into this map, tombstones in overlay delete inherited values
+    pub fn merge(
+        &mut self,
+        overlay: SysctlParameterHashMap<'a>,
+        policy: MergeConflictPolicy,
+    ) -> Result<(), String> {
+        self.merge_at(&mut vec![], overlay, policy)
+    }
+    fn merge_at(
+        &mut self,
+        prefix: &mut Vec<&'a str>,
+        overlay: SysctlParameterHashMap<'a>,
+        policy: MergeConflictPolicy,
+    ) -> Result<(), String> {
+        let SysctlParameterHashMap {
+            items, mut origins, ..
+        } = overlay;
+        for (token, value) in items {
+            prefix.push(token);
+            let origin = origins.remove(token);
+            match (self.items.get_mut(token), value) {
+                // delete inherited value
+                (_, SysctlParameterValue::D) => {
+                    self.items.remove(token);
+                    self.origins.remove(token);
+                }
+                // merge submaps recursively, unless cleared before
+                (Some(SysctlParameterValue::M(base)), SysctlParameterValue::M(child))
+                    if !child.cleared =>
+                {
+                    base.merge_at(prefix, *child, policy)?;
+                }
+                // literal value and submap collide, unless the submap is cleared before
+                (Some(SysctlParameterValue::M(_)), value @ SysctlParameterValue::V(_))
+                | (Some(SysctlParameterValue::V(_)), value @ SysctlParameterValue::M(_))
+                    if !matches!(&value, SysctlParameterValue::M(child) if child.cleared) =>
+                {
+                    match policy {
+                        MergeConflictPolicy::Overlay => {
+                            self.replace(token, value, origin);
+                        }
+                        MergeConflictPolicy::Base => {}
+                        MergeConflictPolicy::Error => {
+                            return Err(format!(
+                                "'{}' is a literal value on one side and a submap on the other",
+                                prefix.join(".")
+                            ))
+                        }
+                    }
+                }
+                // new or replaced value
+                (_, value) => {
+                    self.replace(token, value, origin);
+                }
+            }
+            prefix.pop();
+        }
+        Ok(())
+    }
+    fn replace(&mut self, token: &'a str, value: SysctlParameterValue<'a>, origin: Option<Origin>) {
+        self.items.insert(token, value.without_tombstones());
+        match origin {
+            Some(origin) => self.origins.insert(token, origin),
+            None => self.origins.remove(token),
+        };
+    }
 }
 impl fmt::Debug for SysctlParameterHashMap<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", &self.items)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysctl::{parse_str, parse_str_with, ParseOptions};
+
+    #[test]
+    fn merge() {
+        let mut result = parse_str(
+            "endpoint = localhost:3000
+debug = true
+log.file = /var/log/console.log
+log.limit = 1024",
+        )
+        .unwrap();
+        let overlay = parse_str(
+            "endpoint = localhost:3001
+log.limit = 2048
+log.name = default.log",
+        )
+        .unwrap();
+        assert_eq!(result.merge(overlay, MergeConflictPolicy::Error), Ok(()));
+        assert_eq!(
+            result,
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("endpoint", SysctlParameterValue::V("localhost:3001".into())),
+                    ("debug", SysctlParameterValue::V("true".into())),
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([
+                            (
+                                "file",
+                                SysctlParameterValue::V("/var/log/console.log".into())
+                            ),
+                            ("limit", SysctlParameterValue::V("2048".into())),
+                            ("name", SysctlParameterValue::V("default.log".into()))
+                        ]))
+                    )
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn merge_tombstone() {
+        let mut result = parse_str(
+            "endpoint = localhost:3000
+log.file = /var/log/console.log
+log.limit = 1024",
+        )
+        .unwrap();
+        let overlay = parse_str(
+            "!endpoint
+!log.limit
+!log.name
+new.debug = true
+!new.level",
+        )
+        .unwrap();
+        assert_eq!(result.merge(overlay, MergeConflictPolicy::Error), Ok(()));
+        assert_eq!(
+            result,
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "file",
+                            SysctlParameterValue::V("/var/log/console.log".into())
+                        )]))
+                    ),
+                    (
+                        "new",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "debug",
+                            SysctlParameterValue::V("true".into())
+                        )]))
+                    )
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn merge_cleared() {
+        let overlay = "!log
+log.file = /var/log/console.log
+debug = true
+debug.level = 1";
+        let expected = SysctlParameterHashMap {
+            items: HashMap::from([
+                (
+                    "log",
+                    SysctlParameterValue::from_map(HashMap::from([(
+                        "file",
+                        SysctlParameterValue::V("/var/log/console.log".into()),
+                    )])),
+                ),
+                (
+                    "debug",
+                    SysctlParameterValue::from_map(HashMap::from([(
+                        "level",
+                        SysctlParameterValue::V("1".into()),
+                    )])),
+                ),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(parse_str(overlay).unwrap(), expected);
+
+        let mut result = parse_str(
+            "log = /var/log/console.log
+debug.trace = true",
+        )
+        .unwrap();
+        let merged = result.merge(parse_str(overlay).unwrap(), MergeConflictPolicy::Error);
+        assert_eq!(merged, Ok(()));
+        let debug = HashMap::from([
+            ("trace", SysctlParameterValue::V("true".into())),
+            ("level", SysctlParameterValue::V("1".into())),
+        ]);
+        let mut expected = expected;
+        expected
+            .items
+            .insert("debug", SysctlParameterValue::from_map(debug));
+        assert_eq!(result, expected);
+
+        let mut result = parse_str("log.limit = 1024").unwrap();
+        let merged = result.merge(parse_str(overlay).unwrap(), MergeConflictPolicy::Error);
+        assert_eq!(merged, Ok(()));
+        assert_eq!(result.get(&vec!["log", "limit"]), None);
+        assert_eq!(
+            result.get(&vec!["log", "file"]),
+            Some(&SysctlParameterValue::V("/var/log/console.log".into()))
+        );
+    }
+
+    #[test]
+    fn merge_conflict() {
+        let base = "log = /var/log/console.log
+debug.level = 1";
+        let overlay = "log.file = /var/log/console.log
+debug = true";
+
+        let mut result = parse_str(base).unwrap();
+        let merged = result.merge(parse_str(overlay).unwrap(), MergeConflictPolicy::Overlay);
+        assert_eq!(merged, Ok(()));
+        assert_eq!(
+            result,
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "file",
+                            SysctlParameterValue::V("/var/log/console.log".into())
+                        )]))
+                    ),
+                    ("debug", SysctlParameterValue::V("true".into()))
+                ]),
+                ..Default::default()
+            }
+        );
+
+        let mut result = parse_str(base).unwrap();
+        let merged = result.merge(parse_str(overlay).unwrap(), MergeConflictPolicy::Base);
+        assert_eq!(merged, Ok(()));
+        assert_eq!(result, parse_str(base).unwrap());
+
+        let mut result = parse_str("debug.level = 1").unwrap();
+        let merged = result.merge(
+            parse_str("debug = true").unwrap(),
+            MergeConflictPolicy::Error,
+        );
+        assert_eq!(
+            merged,
+            Err("'debug' is a literal value on one side and a submap on the other".to_string())
+        );
+    }
+
+    #[test]
+    fn merge_origins() {
+        let options = ParseOptions {
+            origins: true,
+            ..Default::default()
+        };
+        let mut result = parse_str_with(
+            "endpoint = localhost:3000
+log.file = /var/log/console.log
+log.limit = 1024",
+            &options,
+        )
+        .unwrap();
+        let overlay = parse_str_with(
+            "!endpoint
+log.limit = 2048",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(result.merge(overlay, MergeConflictPolicy::Error), Ok(()));
+        assert_eq!(result.origin("endpoint"), None);
+        assert_eq!(result.origin("log"), None);
+        assert_eq!(result.origin("log.file").map(|origin| origin.line), Some(2));
+        assert_eq!(
+            result.origin("log.limit"),
+            Some(&Origin {
+                file: None,
+                line: 2,
+                key: 0..9,
+                value: 12..16,
+            })
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    diagnostic::Diagnostic,
+    hashmap::{SysctlParameterHashMap, SysctlParameterValue},
+};
+use std::{borrow::Cow, collections::HashMap, ops::Range};
+
+// error of the value of a dotted token
+struct Error {
+    token: String,
+    // byte range of the reference within the value, if any
+    span: Option<Range<usize>>,
+    message: String,
+}
+
+impl Error {
+    // message with the 1-based column of the reference within the value
+    fn to_message(&self) -> String {
+        match &self.span {
+            Some(span) => format!("{} at column {}", self.message, span.start + 1),
+            None => self.message.clone(),
+        }
+    }
+}
+
+struct Interpolator<'m, 'a> {
+    parameter: &'m SysctlParameterHashMap<'a>,
+    env: &'m HashMap<String, String>,
+    // interpolated values by dotted token
+    resolved: HashMap<String, String>,
+    // dotted tokens being interpolated, to detect cycles
+    stack: Vec<String>,
+}
+
+impl Interpolator<'_, '_> {
+    fn lookup(&mut self, name: &str) -> Result<Option<String>, Error> {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(Some(value.clone()));
+        }
+        // `${other.key}` from the same config
+        let path = name.split(".").collect::<Vec<&str>>();
+        if let Some(SysctlParameterValue::V(value)) = self.parameter.get(&path) {
+            let value = value.to_string();
+            return self.resolve(name, &value).map(Some);
+        }
+        // `${ENV}` from the environment
+        Ok(self.env.get(name).cloned())
+    }
+
+    fn resolve(&mut self, token: &str, value: &str) -> Result<String, Error> {
+        if let Some(start) = self.stack.iter().position(|t| t == token) {
+            let cycle = [&self.stack[start..], &[token.to_string()]].concat();
+            return Err(Error {
+                token: token.to_string(),
+                span: None,
+                message: format!(
+                    "'{}' has a cyclic reference '{}'",
+                    token,
+                    cycle.join(" -> ")
+                ),
+            });
+        }
+        self.stack.push(token.to_string());
+
+        let mut interpolated = String::new();
+        let mut rest = value;
+        while let Some(offset) = rest.find("$") {
+            // byte offset of `$` in the value
+            let start = value.len() - rest.len() + offset;
+            interpolated.push_str(&rest[..offset]);
+            rest = &rest[offset..];
+            if let Some(after) = rest.strip_prefix("$$") {
+                // escaped `$`
+                interpolated.push('$');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("${") {
+                let end = match after.find("}") {
+                    Some(end) => end,
+                    None => {
+                        return Err(Error {
+                            token: token.to_string(),
+                            span: Some(start..value.len()),
+                            message: format!("'{}' has an unterminated reference", token),
+                        })
+                    }
+                };
+                let name = after[..end].trim();
+                match self.lookup(name)? {
+                    Some(value) => interpolated.push_str(&value),
+                    None => {
+                        return Err(Error {
+                            token: token.to_string(),
+                            span: Some(start..start + end + 3),
+                            message: format!(
+                                "'{}' has an undefined reference '${{{}}}'",
+                                token, name
+                            ),
+                        })
+                    }
+                }
+                rest = &after[end + 1..];
+            } else {
+                // lone `$`
+                interpolated.push('$');
+                rest = &rest[1..];
+            }
+        }
+        interpolated.push_str(rest);
+
+        self.stack.pop();
+        self.resolved
+            .insert(token.to_string(), interpolated.clone());
+        Ok(interpolated)
+    }
+}
+
+// resolve `${other.key}` from the same config, then `${ENV}` from env, and `$$` as `$`
+pub fn interpolate(
+    parameter: &mut SysctlParameterHashMap,
+    env: &HashMap<String, String>,
+) -> Result<(), String> {
+    interpolate_values(parameter, env).map_err(|err| err.to_message())
+}
+
+// same as interpolate, with the error located at the value of its token if the origin is tracked
+pub fn interpolate_diagnostic(
+    parameter: &mut SysctlParameterHashMap,
+    env: &HashMap<String, String>,
+) -> Result<(), Box<Diagnostic>> {
+    interpolate_values(parameter, env).map_err(|err| {
+        let origin = match parameter.origin(&err.token) {
+            Some(origin) => origin,
+            None => return Box::new(Diagnostic::error("invalid-reference", err.to_message())),
+        };
+        // the reference within the value span, or the whole value
+        let (column, length) = match &err.span {
+            Some(span) if origin.value.start + span.end <= origin.value.end => {
+                (origin.value.start + span.start, span.len())
+            }
+            _ => (origin.value.start, origin.value.len()),
+        };
+        Box::new(Diagnostic {
+            file: origin.file.clone(),
+            line: origin.line,
+            column: column + 1,
+            length,
+            token: Some(err.token),
+            ..Diagnostic::error("invalid-reference", err.message)
+        })
+    })
+}
+
+fn interpolate_values(
+    parameter: &mut SysctlParameterHashMap,
+    env: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let leaves = parameter
+        .leaves()
+        .into_iter()
+        .filter(|(_, value)| value.contains("$"))
+        .map(|(path, value)| (path, value.to_string()))
+        .collect::<Vec<_>>();
+    let mut interpolated = vec![];
+    let mut interpolator = Interpolator {
+        parameter,
+        env,
+        resolved: HashMap::new(),
+        stack: vec![],
+    };
+    for (path, value) in leaves {
+        let value = interpolator.resolve(&path.join("."), &value)?;
+        interpolated.push((path, value));
+    }
+    for (path, value) in interpolated {
+        let origin = parameter.origin(&path.join(".")).cloned();
+        parameter.insert_with_origin(&path, SysctlParameterValue::V(Cow::Owned(value)), origin);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysctl::parse_str;
+
+    #[test]
+    fn sample() {
+        let mut result = parse_str(
+            "endpoint = localhost:${PORT}
+backup.endpoint = ${endpoint}
+log.file = ${LOG_DIR}/console.log
+log.price = $$100 $5",
+        )
+        .unwrap();
+        let env = HashMap::from([
+            ("PORT".to_string(), "3000".to_string()),
+            ("LOG_DIR".to_string(), "/var/log".to_string()),
+            ("endpoint".to_string(), "shadowed".to_string()),
+        ]);
+        assert_eq!(interpolate(&mut result, &env), Ok(()));
+        assert_eq!(
+            result,
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    (
+                        "backup",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "endpoint",
+                            SysctlParameterValue::V("localhost:3000".into())
+                        )]))
+                    ),
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([
+                            (
+                                "file",
+                                SysctlParameterValue::V("/var/log/console.log".into())
+                            ),
+                            ("price", SysctlParameterValue::V("$100 $5".into()))
+                        ]))
+                    )
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn undefined() {
+        let mut result = parse_str("log.file = ${LOG_DIR}/console.log").unwrap();
+        assert_eq!(
+            interpolate(&mut result, &HashMap::new()),
+            Err("'log.file' has an undefined reference '${LOG_DIR}' at column 1".to_string())
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        let mut result = parse_str("log.file = /var/${LOG_DIR/console.log").unwrap();
+        assert_eq!(
+            interpolate(&mut result, &HashMap::new()),
+            Err("'log.file' has an unterminated reference at column 6".to_string())
+        );
+    }
+
+    #[test]
+    fn diagnostic() {
+        let options = crate::sysctl::ParseOptions {
+            origins: true,
+            ..Default::default()
+        };
+        let mut result = crate::sysctl::parse_str_with(
+            "endpoint = localhost:${PORT}\nlog.file = /var/${LOG_DIR}/console.log",
+            &options,
+        )
+        .unwrap();
+        let env = HashMap::from([("PORT".to_string(), "3000".to_string())]);
+        let diagnostic = interpolate_diagnostic(&mut result, &env).unwrap_err();
+        assert_eq!(
+            diagnostic.message,
+            "'log.file' has an undefined reference '${LOG_DIR}'"
+        );
+        assert_eq!(diagnostic.code, "invalid-reference");
+        assert_eq!(diagnostic.token, Some("log.file".to_string()));
+        assert_eq!(
+            (diagnostic.line, diagnostic.column, diagnostic.length),
+            (2, 17, 10)
+        );
+
+        // interpolated values keep their origin
+        let mut result =
+            crate::sysctl::parse_str_with("endpoint = localhost:${PORT}", &options).unwrap();
+        assert_eq!(interpolate_diagnostic(&mut result, &env), Ok(()));
+        assert_eq!(result.origin("endpoint").map(|origin| origin.line), Some(1));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut result = parse_str(
+            "a = ${b}
+b = x${c}
+c = ${a}",
+        )
+        .unwrap();
+        assert_eq!(
+            interpolate(&mut result, &HashMap::new()),
+            Err("'a' has a cyclic reference 'a -> b -> c -> a'".to_string())
+        );
+    }
+}
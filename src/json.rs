@@ -0,0 +1,234 @@
+use crate::{
+    hashmap::{SysctlParameterHashMap, SysctlParameterValue},
+    typed::Value,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    // members in insertion order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<K: Into<String>>(members: Vec<(K, Json)>) -> Self {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    }
+    pub fn string<S: Into<String>>(value: S) -> Self {
+        Json::String(value.into())
+    }
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    // indented with 2 spaces
+    pub fn pretty(&self) -> String {
+        let mut output = String::new();
+        self.write_pretty(&mut output, 0);
+        output
+    }
+    fn write_pretty(&self, output: &mut String, indent: usize) {
+        let padding = "  ".repeat(indent + 1);
+        match self {
+            Json::Array(items) if !items.is_empty() => {
+                output.push_str("[\n");
+                for (index, item) in items.iter().enumerate() {
+                    output.push_str(&padding);
+                    item.write_pretty(output, indent + 1);
+                    output.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                output.push_str(&"  ".repeat(indent));
+                output.push(']');
+            }
+            Json::Object(members) if !members.is_empty() => {
+                output.push_str("{\n");
+                for (index, (key, value)) in members.iter().enumerate() {
+                    output.push_str(&padding);
+                    output.push_str(&quote(key));
+                    output.push_str(": ");
+                    value.write_pretty(output, indent + 1);
+                    output.push_str(if index + 1 < members.len() {
+                        ",\n"
+                    } else {
+                        "\n"
+                    });
+                }
+                output.push_str(&"  ".repeat(indent));
+                output.push('}');
+            }
+            value => output.push_str(&value.to_string()),
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Integer(value) => write!(f, "{}", value),
+            Json::Float(value) if value.is_finite() => write!(f, "{:?}", value),
+            Json::Float(_) => write!(f, "null"),
+            Json::String(value) => write!(f, "{}", quote(value)),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", quote(key), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl From<&SysctlParameterValue<'_>> for Json {
+    fn from(value: &SysctlParameterValue<'_>) -> Self {
+        match value {
+            SysctlParameterValue::V(value) => Json::string(value.as_ref()),
+            SysctlParameterValue::M(child) => Json::from(child.as_ref()),
+            SysctlParameterValue::D => Json::Null,
+        }
+    }
+}
+
+// object with tokens sorted
+impl From<&SysctlParameterHashMap<'_>> for Json {
+    fn from(value: &SysctlParameterHashMap<'_>) -> Self {
+        let mut members = value
+            .items
+            .iter()
+            .map(|(&token, value)| (token.to_string(), Json::from(value)))
+            .collect::<Vec<(String, Json)>>();
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        Json::Object(members)
+    }
+}
+
+impl From<&Value> for Json {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Bool(value) => Json::Bool(*value),
+            // beyond i64 as float
+            Value::Integer(value) => match i64::try_from(*value) {
+                Ok(value) => Json::Integer(value),
+                Err(_) => Json::Float(*value as f64),
+            },
+            Value::Float(value) => Json::Float(*value),
+            Value::String(value) => Json::string(value.as_str()),
+            Value::List(items) => Json::Array(items.iter().map(Json::from).collect()),
+            Value::Map(map) => Json::Object(
+                map.iter()
+                    .map(|(token, value)| (token.clone(), Json::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysctl::parse_str;
+
+    #[test]
+    fn sysctl() {
+        let result = parse_str(
+            "endpoint = localhost:3000
+log.name = \"default\\tlog\"
+log.file = /var/log/console.log
+!debug",
+        )
+        .unwrap();
+        assert_eq!(
+            Json::from(&result).to_string(),
+            r#"{"debug":null,"endpoint":"localhost:3000","log":{"file":"/var/log/console.log","name":"\"default\\tlog\""}}"#
+        );
+        assert_eq!(
+            Json::from(&result).pretty(),
+            r#"{
+  "debug": null,
+  "endpoint": "localhost:3000",
+  "log": {
+    "file": "/var/log/console.log",
+    "name": "\"default\\tlog\""
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn values() {
+        let value = Json::object(vec![
+            (
+                "array",
+                Json::Array(vec![Json::Integer(1), Json::Float(0.5)]),
+            ),
+            ("empty", Json::Array(vec![])),
+            ("control", Json::string("\u{1}\n")),
+            ("bool", Json::Bool(true)),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"array":[1,0.5],"empty":[],"control":"\u0001\n","bool":true}"#
+        );
+        assert_eq!(value.get("bool"), Some(&Json::Bool(true)));
+        assert_eq!(value.get("none"), None);
+    }
+
+    #[test]
+    fn typed() {
+        let sysctl_conf =
+            parse_str("debug = true\nlog.limit = 18446744073709551615\nports = 1,2").unwrap();
+        let schema =
+            crate::schema::parse_str("debug => bool\nlog.limit => integer\nports => list(integer)")
+                .unwrap();
+        let result = crate::typed::convert(&sysctl_conf, &schema).unwrap();
+        assert_eq!(
+            Json::from(&result).to_string(),
+            r#"{"debug":true,"log":{"limit":1.8446744073709552e19},"ports":[1,2]}"#
+        );
+    }
+}
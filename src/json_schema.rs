@@ -0,0 +1,342 @@
+use crate::{
+    constraint::{Condition, Operand, Operator, Rule},
+    json::Json,
+    schema::{SchemaHashMap, SchemaType},
+    typed::{convert_value, Value},
+};
+use std::collections::HashMap;
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// keywords from constraints, keyed by the dotted token they apply to
+type Keywords = HashMap<String, Vec<(String, Json)>>;
+
+// members of a JSON Schema for a literal value of a schema type, as converted by `parse --schema`
+fn type_members(schema_type: &SchemaType) -> Vec<(String, Json)> {
+    let members = match schema_type {
+        SchemaType::String() => vec![("type", Json::string("string"))],
+        SchemaType::Bool() => vec![("type", Json::string("boolean"))],
+        SchemaType::Integer() | SchemaType::Size() | SchemaType::Duration() => vec![
+            ("type", Json::string("integer")),
+            ("minimum", Json::Integer(0)),
+        ],
+        SchemaType::Float() => vec![("type", Json::string("number"))],
+        SchemaType::Normalized(normalized) | SchemaType::Optional(normalized) => {
+            return type_members(normalized)
+        }
+        SchemaType::List(item) => vec![
+            ("type", Json::string("array")),
+            ("items", Json::Object(type_members(item))),
+        ],
+        SchemaType::Map(_) | SchemaType::Object() => vec![("type", Json::string("object"))],
+    };
+    members
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
+
+// keyword of `token operator literal`
+fn bound(operator: Operator, literal: &Value) -> Option<(String, Json)> {
+    let numeric = matches!(literal, Value::Integer(_) | Value::Float(_));
+    let (keyword, value) = match operator {
+        Operator::Eq => ("const", Json::from(literal)),
+        Operator::Ne => ("not", Json::object(vec![("const", Json::from(literal))])),
+        Operator::Lt if numeric => ("exclusiveMaximum", Json::from(literal)),
+        Operator::Le if numeric => ("maximum", Json::from(literal)),
+        Operator::Gt if numeric => ("exclusiveMinimum", Json::from(literal)),
+        Operator::Ge if numeric => ("minimum", Json::from(literal)),
+        _ => return None,
+    };
+    Some((keyword.to_string(), value))
+}
+
+fn flip(operator: Operator) -> Operator {
+    match operator {
+        Operator::Lt => Operator::Gt,
+        Operator::Le => Operator::Ge,
+        Operator::Gt => Operator::Lt,
+        Operator::Ge => Operator::Le,
+        operator => operator,
+    }
+}
+
+// parent and last token of sibling tokens, or None if they are not siblings
+fn siblings<'a>(tokens: &[&'a str]) -> Option<(&'a str, Vec<&'a str>)> {
+    let split = tokens
+        .iter()
+        .map(|token| token.rsplit_once(".").unwrap_or(("", token)))
+        .collect::<Vec<(&str, &str)>>();
+    let parent = split.first()?.0;
+    if split.iter().any(|(other, _)| *other != parent) {
+        return None;
+    }
+    Some((parent, split.into_iter().map(|(_, last)| last).collect()))
+}
+
+// constraints which JSON Schema can express: comparisons of a token with a literal on the token,
+// and `token requires token...` and `token conflicts token...` of siblings on their parent
+fn constraint_keywords(schema: &SchemaHashMap) -> Keywords {
+    let mut keywords: Keywords = HashMap::new();
+    let mut dependent_required: HashMap<&str, Vec<(String, Json)>> = HashMap::new();
+    let mut dependent_schemas: HashMap<&str, Vec<(String, Json)>> = HashMap::new();
+    for constraint in &schema.constraints {
+        match &constraint.rule {
+            Rule::Compare(comparison) => {
+                let pairs = comparison.operands.windows(2).zip(&comparison.operators);
+                for (operands, &operator) in pairs {
+                    let (token, operator, literal) = match operands {
+                        [Operand::Token(token, None), Operand::Literal(literal)] => {
+                            (token, operator, literal)
+                        }
+                        [Operand::Literal(literal), Operand::Token(token, None)] => {
+                            (token, flip(operator), literal)
+                        }
+                        _ => continue,
+                    };
+                    if let Some(bound) = bound(operator, literal) {
+                        keywords.entry(token.clone()).or_default().push(bound);
+                    }
+                }
+            }
+            Rule::Requires(Condition::Present(token), tokens) => {
+                let all = [
+                    vec![token.as_str()],
+                    tokens.iter().map(String::as_str).collect(),
+                ]
+                .concat();
+                if let Some((parent, names)) = siblings(&all) {
+                    let required = names[1..].iter().map(|name| Json::string(*name)).collect();
+                    dependent_required
+                        .entry(parent)
+                        .or_default()
+                        .push((names[0].to_string(), Json::Array(required)));
+                }
+            }
+            Rule::Conflicts(token, tokens) => {
+                let all = [
+                    vec![token.as_str()],
+                    tokens.iter().map(String::as_str).collect(),
+                ]
+                .concat();
+                if let Some((parent, names)) = siblings(&all) {
+                    let conflicting = names[1..]
+                        .iter()
+                        .map(|name| {
+                            Json::object(vec![("required", Json::Array(vec![Json::string(*name)]))])
+                        })
+                        .collect();
+                    let forbidden = Json::object(vec![(
+                        "not",
+                        Json::object(vec![("anyOf", Json::Array(conflicting))]),
+                    )]);
+                    dependent_schemas
+                        .entry(parent)
+                        .or_default()
+                        .push((names[0].to_string(), forbidden));
+                }
+            }
+            // comparisons between tokens and conditional requirements are left out
+            _ => {}
+        }
+    }
+    for (parent, members) in dependent_required {
+        keywords
+            .entry(parent.to_string())
+            .or_default()
+            .push(("dependentRequired".to_string(), Json::Object(members)));
+    }
+    for (parent, members) in dependent_schemas {
+        keywords
+            .entry(parent.to_string())
+            .or_default()
+            .push(("dependentSchemas".to_string(), Json::Object(members)));
+    }
+    keywords
+}
+
+// a token is required unless it is optional or only has optional tokens below it
+fn is_required(node: &SchemaHashMap) -> bool {
+    match &node.schema_type {
+        Some(SchemaType::Optional(_)) => false,
+        Some(_) => true,
+        None => node.items.values().any(is_required),
+    }
+}
+
+// append members, moving those whose keys are already present into `allOf`
+fn merge(members: &mut Vec<(String, Json)>, added: Vec<(String, Json)>) {
+    let mut all_of = vec![];
+    for (key, value) in added {
+        if members.iter().any(|(existing, _)| *existing == key) {
+            all_of.push(Json::Object(vec![(key, value)]));
+        } else {
+            members.push((key, value));
+        }
+    }
+    if !all_of.is_empty() {
+        members.push(("allOf".to_string(), Json::Array(all_of)));
+    }
+}
+
+fn node_schema(
+    node: &SchemaHashMap,
+    token: &str,
+    keywords: &Keywords,
+    defs: &mut Vec<(String, Json)>,
+) -> Vec<(String, Json)> {
+    let mut members = match &node.schema_type {
+        Some(schema_type) => type_members(schema_type),
+        None => type_members(&SchemaType::Object()),
+    };
+    let annotations = &node.annotations;
+    if let Some(description) = &annotations.description {
+        members.push((
+            "description".to_string(),
+            Json::string(description.as_str()),
+        ));
+    }
+    let default = match (&annotations.default, &node.schema_type) {
+        (Some(default), Some(schema_type)) => convert_value(token, default, schema_type).ok(),
+        _ => None,
+    };
+    if let Some(default) = default {
+        members.push(("default".to_string(), Json::from(&default)));
+    }
+    if annotations.deprecated {
+        members.push(("deprecated".to_string(), Json::Bool(true)));
+    }
+
+    let mut names = node.items.keys().collect::<Vec<&&str>>();
+    names.sort();
+    if !names.is_empty() {
+        let mut properties = vec![];
+        let mut required = vec![];
+        for name in names {
+            let child = &node.items[*name];
+            let child_token = match token {
+                "" => name.to_string(),
+                token => format!("{}.{}", token, name),
+            };
+            let child_schema = node_schema(child, &child_token, keywords, defs);
+            properties.push((name.to_string(), Json::Object(child_schema)));
+            if is_required(child) {
+                required.push(Json::string(*name));
+            }
+        }
+        members.push(("properties".to_string(), Json::Object(properties)));
+        if !required.is_empty() {
+            members.push(("required".to_string(), Json::Array(required)));
+        }
+    }
+    // literal values at any depth below a map, by a definition referring to itself
+    if let Some(SchemaType::Map(item)) = node.schema_type.as_ref().map(SchemaType::base) {
+        let reference = Json::object(vec![("$ref", Json::string(format!("#/$defs/{}", token)))]);
+        let nested = Json::object(vec![
+            ("type", Json::string("object")),
+            ("additionalProperties", reference.clone()),
+        ]);
+        defs.push((
+            token.to_string(),
+            Json::object(vec![(
+                "anyOf",
+                Json::Array(vec![Json::Object(type_members(item)), nested]),
+            )]),
+        ));
+        members.push(("additionalProperties".to_string(), reference));
+    }
+    if let Some(added) = keywords.get(token) {
+        merge(&mut members, added.clone());
+    }
+    members
+}
+
+// JSON Schema of values printed by `parse --schema`, with dotted tokens as nested properties
+pub fn to_json_schema(schema: &SchemaHashMap) -> Json {
+    let keywords = constraint_keywords(schema);
+    let mut defs = vec![];
+    let mut members = vec![("$schema".to_string(), Json::string(DIALECT))];
+    members.extend(node_schema(schema, "", &keywords, &mut defs));
+    if !defs.is_empty() {
+        members.push(("$defs".to_string(), Json::Object(defs)));
+    }
+    Json::Object(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_schema() {
+        let schema = crate::schema::parse_str(
+            "# @desc Endpoint to listen on
+endpoint => string
+debug => normalized(bool)
+log {
+    # @desc Maximum size of a log file
+    # @default=64K
+    limit => size
+    # @deprecated(use=limit)
+    size => optional(size)
+    file => optional(string)
+    syslog => optional(bool)
+    ratio => float
+    constraint 0 < ratio <= 1
+    constraint file conflicts syslog
+}
+ports => list(integer)
+constraint endpoint != \"\"
+constraint log.limit >= 1024
+constraint log.limit < log.size",
+        )
+        .unwrap();
+        assert_eq!(
+            to_json_schema(&schema).to_string(),
+            [
+                r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","type":"object","properties":{"#,
+                r#""debug":{"type":"boolean"},"#,
+                r#""endpoint":{"type":"string","description":"Endpoint to listen on","not":{"const":""}},"#,
+                r#""log":{"type":"object","properties":{"#,
+                r#""file":{"type":"string"},"#,
+                r#""limit":{"type":"integer","minimum":0,"description":"Maximum size of a log file","default":65536,"allOf":[{"minimum":1024}]},"#,
+                r#""ratio":{"type":"number","exclusiveMinimum":0,"maximum":1},"#,
+                r#""size":{"type":"integer","minimum":0,"deprecated":true},"#,
+                r#""syslog":{"type":"boolean"}},"#,
+                r#""required":["limit","ratio"],"#,
+                r#""dependentSchemas":{"file":{"not":{"anyOf":[{"required":["syslog"]}]}}}},"#,
+                r#""ports":{"type":"array","items":{"type":"integer","minimum":0}}},"#,
+                r#""required":["debug","endpoint","log","ports"]}"#,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn json_schema_map() {
+        let schema = crate::schema::parse_str(
+            "net.conf => map(integer)
+net.conf.lo.name => string
+app => optional(object)
+log.file => optional(string)
+log.path => optional(string)
+constraint log.file requires log.path
+constraint log.file requires net.conf.lo.name",
+        )
+        .unwrap();
+        assert_eq!(
+            to_json_schema(&schema).to_string(),
+            [
+                r##"{"$schema":"https://json-schema.org/draft/2020-12/schema","type":"object","properties":{"##,
+                r##""app":{"type":"object"},"##,
+                r##""log":{"type":"object","properties":{"file":{"type":"string"},"path":{"type":"string"}},"dependentRequired":{"file":["path"]}},"##,
+                r##""net":{"type":"object","properties":{"conf":{"type":"object","properties":{"##,
+                r##""lo":{"type":"object","properties":{"name":{"type":"string"}},"required":["name"]}},"##,
+                r##""required":["lo"],"additionalProperties":{"$ref":"#/$defs/net.conf"}}},"required":["conf"]}},"##,
+                r##""required":["net"],"##,
+                r##""$defs":{"net.conf":{"anyOf":[{"type":"integer","minimum":0},{"type":"object","additionalProperties":{"$ref":"#/$defs/net.conf"}}]}}}"##,
+            ]
+            .concat()
+        );
+    }
+}
@@ -1,4 +1,16 @@
+pub mod annotation;
+pub mod constraint;
+pub mod diagnostic;
+pub mod doc;
+pub mod format;
 pub mod hashmap;
+pub mod interpolate;
+pub mod json;
+pub mod json_schema;
+pub mod lint;
+pub mod normalize;
 pub mod schema;
+pub mod source;
 pub mod sysctl;
+pub mod typed;
 pub mod validator;
@@ -0,0 +1,501 @@
+use crate::{
+    diagnostic::{Diagnostic, Severity},
+    source::parse_include,
+    sysctl::{self, ParseOptions},
+};
+use std::collections::{HashMap, HashSet};
+
+// rule ID, default severity and description
+pub const RULES: &[(&str, Severity, &str)] = &[
+    (
+        "ignored-token",
+        Severity::Warning,
+        "token begins with `-` and is ignored",
+    ),
+    (
+        "duplicate-token",
+        Severity::Warning,
+        "token is set more than once",
+    ),
+    (
+        "submap-collision",
+        Severity::Error,
+        "token is both a literal value and a submap",
+    ),
+    ("empty-value", Severity::Warning, "value is empty"),
+    (
+        "token-case",
+        Severity::Info,
+        "token has uppercase characters",
+    ),
+    (
+        "token-characters",
+        Severity::Warning,
+        "token has characters other than alphanumerics, `_`, `-`, `.`, `/` and `*`",
+    ),
+    (
+        "tab-whitespace",
+        Severity::Info,
+        "tab is used as whitespace around token",
+    ),
+    ("crlf", Severity::Warning, "line ends with CRLF"),
+];
+
+// severity of each rule, or None if disabled
+pub struct LintConfig {
+    pub rules: HashMap<&'static str, Option<Severity>>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            rules: RULES
+                .iter()
+                .map(|&(rule, severity, _)| (rule, Some(severity)))
+                .collect(),
+        }
+    }
+}
+
+fn find_rule(rule: &str) -> Result<&'static str, String> {
+    RULES
+        .iter()
+        .find(|(id, _, _)| *id == rule)
+        .map(|(id, _, _)| *id)
+        .ok_or_else(|| format!("'{}' is not a lint rule", rule))
+}
+
+impl LintConfig {
+    // config in sysctl.conf(5) format such as `duplicate-token = off` or `token-case = error`
+    pub fn parse_str(config: &str) -> Result<LintConfig, String> {
+        let mut lint_config = LintConfig::default();
+        let parameter = sysctl::parse_str(config)?;
+        for (path, value) in parameter.leaves() {
+            let rule = find_rule(&path.join("."))?;
+            let severity = match value {
+                "off" => None,
+                value => match Severity::parse(value) {
+                    Some(severity) => Some(severity),
+                    None => {
+                        return Err(format!(
+                        "'{}' has an invalid severity '{}' (must be off, info, warning or error)",
+                        rule, value
+                    ))
+                    }
+                },
+            };
+            lint_config.rules.insert(rule, severity);
+        }
+        Ok(lint_config)
+    }
+}
+
+// `# lint: disable=rule,...`, `# lint: enable=rule,...` or `# lint: disable-next-line=rule,...`
+fn parse_directive(comment: &str) -> Option<(&str, Vec<&str>)> {
+    let directive = comment[1..].trim().strip_prefix("lint:")?;
+    let (action, rules) = directive.trim().split_once("=")?;
+    let rules = rules.split(",").map(|rule| rule.trim()).collect();
+    Some((action.trim(), rules))
+}
+
+struct Linter<'c> {
+    config: &'c LintConfig,
+    options: &'c ParseOptions,
+    disabled: HashSet<String>,
+    disabled_next_line: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+    // first line of each token, and of each submap
+    tokens: HashMap<String, usize>,
+    submaps: HashMap<String, usize>,
+}
+
+impl Linter<'_> {
+    fn report(
+        &mut self,
+        rule: &str,
+        line: usize,
+        column: usize,
+        token: Option<&str>,
+        message: String,
+    ) {
+        if self.disabled.contains(rule) || self.disabled_next_line.contains(rule) {
+            return;
+        }
+        if let Some(&Some(severity)) = self.config.rules.get(rule) {
+            self.diagnostics.push(Diagnostic {
+                severity,
+                line,
+                column,
+                token: token.map(|token| token.to_string()),
+                ..Diagnostic::error(rule, message)
+            });
+        }
+    }
+
+    fn apply_directive(&mut self, comment: &str, line: usize) -> Result<bool, String> {
+        let (action, rules) = match parse_directive(comment) {
+            Some(directive) => directive,
+            None => return Ok(false),
+        };
+        for rule in rules {
+            let rule = find_rule(rule).map_err(|err| format!("{} at line {}", err, line))?;
+            match action {
+                "disable" => {
+                    self.disabled.insert(rule.to_string());
+                }
+                "enable" => {
+                    self.disabled.remove(rule);
+                }
+                "disable-next-line" => {
+                    self.disabled_next_line.insert(rule.to_string());
+                }
+                _ => {
+                    return Err(format!(
+                        "'{}' is not a lint directive (must be disable, enable or disable-next-line) at line {}",
+                        action, line
+                    ))
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn lint_entry(&mut self, raw: &str, line: usize) {
+        let (raw_token, raw_value) = raw.split_once("=").unwrap();
+        let token = raw_token.trim();
+        let column = raw.find(token).unwrap_or(0) + 1;
+
+        // first tab around the token or before the value
+        let value_start = raw.len() - raw_value.trim_start().len();
+        if let Some(tab) = raw[..value_start].find('\t') {
+            self.report(
+                "tab-whitespace",
+                line,
+                tab + 1,
+                Some(token),
+                format!("'{}' has a tab as whitespace", token),
+            );
+        }
+        if token.chars().any(|c| c.is_uppercase()) {
+            self.report(
+                "token-case",
+                line,
+                column,
+                Some(token),
+                format!("'{}' has uppercase characters", token),
+            );
+        }
+        if let Some(c) = token
+            .chars()
+            .find(|&c| !(c.is_alphanumeric() || "_-./*".contains(c)))
+        {
+            self.report(
+                "token-characters",
+                line,
+                column,
+                Some(token),
+                format!("'{}' has an unusual character '{}'", token, c),
+            );
+        }
+        // value as parsed, without quotes or an inline comment
+        let empty =
+            sysctl::parse_value(raw_value, self.options).is_ok_and(|value| value.is_empty());
+        if empty {
+            let (_, value) = sysctl::spans(raw, self.options);
+            self.report(
+                "empty-value",
+                line,
+                value.start + 1,
+                Some(token),
+                format!("'{}' has an empty value", token),
+            );
+        }
+
+        if let Some(&first) = self.tokens.get(token) {
+            self.report(
+                "duplicate-token",
+                line,
+                column,
+                Some(token),
+                format!("'{}' is already set at line {}", token, first),
+            );
+        } else if let Some(&first) = self.submaps.get(token) {
+            self.report(
+                "submap-collision",
+                line,
+                column,
+                Some(token),
+                format!("'{}' is already a submap at line {}", token, first),
+            );
+        }
+        // every prefix of the token is a submap
+        let path = token.split(".").collect::<Vec<&str>>();
+        for end in 1..path.len() {
+            let prefix = path[..end].join(".");
+            if let Some(&first) = self.tokens.get(&prefix) {
+                self.report(
+                    "submap-collision",
+                    line,
+                    column,
+                    Some(token),
+                    format!("'{}' is already a literal value at line {}", prefix, first),
+                );
+            }
+            self.submaps.entry(prefix).or_insert(line);
+        }
+        self.tokens.entry(token.to_string()).or_insert(line);
+    }
+
+    // a tombstone deletes the token and everything below it, which can be set again
+    fn apply_tombstone(&mut self, token: &str) {
+        let below = format!("{}.", token);
+        let deleted = |key: &String| key == token || key.starts_with(&below);
+        self.tokens.retain(|key, _| !deleted(key));
+        self.submaps.retain(|key, _| !deleted(key));
+    }
+}
+
+// lint a sysctl.conf(5) string, which is expected to be parsed successfully
+pub fn lint_str(
+    sysctl_conf: &str,
+    options: &ParseOptions,
+    config: &LintConfig,
+) -> Result<Vec<Diagnostic>, String> {
+    let mut linter = Linter {
+        config,
+        options,
+        disabled: HashSet::new(),
+        disabled_next_line: HashSet::new(),
+        diagnostics: vec![],
+        tokens: HashMap::new(),
+        submaps: HashMap::new(),
+    };
+    let mut continued = false;
+    for (index, raw) in sysctl_conf.split_inclusive("\n").enumerate() {
+        let line = index + 1;
+        if raw.ends_with("\r\n") {
+            linter.report(
+                "crlf",
+                line,
+                raw.len() - 1,
+                None,
+                "line ends with CRLF".to_string(),
+            );
+        }
+        let raw = raw.trim_end_matches(['\r', '\n']);
+        let trimmed = raw.trim();
+        let is_comment = trimmed.starts_with("#") || trimmed.starts_with(";");
+        let was_continued = continued;
+        continued =
+            options.line_continuation && trimmed.ends_with("\\") && (was_continued || !is_comment);
+        // continued physical lines are a part of value
+        if was_continued {
+            continue;
+        }
+
+        if is_comment {
+            if linter.apply_directive(trimmed, line)? {
+                continue;
+            }
+        } else if trimmed.starts_with("-") {
+            linter.report(
+                "ignored-token",
+                line,
+                raw.find("-").unwrap() + 1,
+                None,
+                format!("'{}' is ignored since it begins with `-`", trimmed),
+            );
+        } else if trimmed.starts_with("!") && !trimmed.contains("=") {
+            linter.apply_tombstone(trimmed[1..].trim());
+        } else if options.include && parse_include(trimmed).is_some() {
+            // include directive
+        } else if trimmed.contains("=") {
+            linter.lint_entry(raw, line);
+        }
+        if !trimmed.is_empty() {
+            linter.disabled_next_line.clear();
+        }
+    }
+    // source lines for columns counted in other units such as SARIF
+    let lines = sysctl_conf.lines().collect::<Vec<&str>>();
+    for diagnostic in &mut linter.diagnostics {
+        diagnostic.source = lines.get(diagnostic.line - 1).map(|line| line.to_string());
+    }
+    Ok(linter.diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(sysctl_conf: &str) -> Vec<String> {
+        lint_str(
+            sysctl_conf,
+            &ParseOptions::default(),
+            &LintConfig::default(),
+        )
+        .unwrap()
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn rules() {
+        let result = lint(
+            "endpoint = localhost:3000
+-debug = true
+endpoint = localhost:3001
+log = /var/log/console.log
+log.file = /var/log/console.log
+net.ipv4 = 1
+net = 0
+app.name =
+App.Name = default.log
+app.na$me = default.log
+app.limit\t= 1024
+app.size = 1024\r
+",
+        );
+        assert_eq!(
+            result,
+            vec![
+                "2:1: warning[ignored-token]: '-debug = true' is ignored since it begins with `-`",
+                "3:1: warning[duplicate-token]: 'endpoint' is already set at line 1",
+                "5:1: error[submap-collision]: 'log' is already a literal value at line 4",
+                "7:1: error[submap-collision]: 'net' is already a submap at line 6",
+                "8:11: warning[empty-value]: 'app.name' has an empty value",
+                "9:1: info[token-case]: 'App.Name' has uppercase characters",
+                "10:1: warning[token-characters]: 'app.na$me' has an unusual character '$'",
+                "11:10: info[tab-whitespace]: 'app.limit' has a tab as whitespace",
+                "12:16: warning[crlf]: line ends with CRLF",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_value() {
+        let options = ParseOptions {
+            quoting: true,
+            inline_comments: true,
+            ..Default::default()
+        };
+        let result = lint_str(
+            "app.name = \"\"  # none
+app.mode = '' ;none
+app.label = \" \"
+app.color = #ffffff # white
+",
+            &options,
+            &LintConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "1:12: warning[empty-value]: 'app.name' has an empty value",
+                "2:12: warning[empty-value]: 'app.mode' has an empty value",
+            ]
+        );
+    }
+
+    #[test]
+    fn tombstones() {
+        let result = lint(
+            "endpoint = localhost:3000
+!endpoint
+endpoint = localhost:3001
+log.file = /var/log/console.log
+! log
+log = off
+endpoint = localhost:3002
+",
+        );
+        assert_eq!(
+            result,
+            vec!["7:1: warning[duplicate-token]: 'endpoint' is already set at line 3"]
+        );
+    }
+
+    #[test]
+    fn tab_column() {
+        let result = lint("  \tendpoint = localhost:3000\nlog.file =\t/var/log/console.log\n");
+        assert_eq!(
+            result,
+            vec![
+                "1:3: info[tab-whitespace]: 'endpoint' has a tab as whitespace",
+                "2:11: info[tab-whitespace]: 'log.file' has a tab as whitespace",
+            ]
+        );
+    }
+
+    #[test]
+    fn directives() {
+        let result = lint(
+            "# lint: disable=duplicate-token,empty-value
+endpoint = localhost:3000
+endpoint = localhost:3001
+log.name =
+# lint: enable=duplicate-token
+endpoint = localhost:3002
+; lint: disable-next-line=duplicate-token
+
+endpoint = localhost:3003
+endpoint = localhost:3004
+",
+        );
+        assert_eq!(
+            result,
+            vec![
+                "6:1: warning[duplicate-token]: 'endpoint' is already set at line 2",
+                "10:1: warning[duplicate-token]: 'endpoint' is already set at line 2",
+            ]
+        );
+
+        let result = lint_str(
+            "# lint: disable=no-such-rule",
+            &ParseOptions::default(),
+            &LintConfig::default(),
+        );
+        assert_eq!(
+            result,
+            Err("'no-such-rule' is not a lint rule at line 1".to_string())
+        );
+    }
+
+    #[test]
+    fn config() {
+        let config = LintConfig::parse_str(
+            "duplicate-token = off
+token-case = error",
+        )
+        .unwrap();
+        let result = lint_str(
+            "endpoint = localhost:3000
+endpoint = localhost:3001
+Debug = true",
+            &ParseOptions::default(),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec!["3:1: error[token-case]: 'Debug' has uppercase characters"]
+        );
+
+        assert_eq!(
+            LintConfig::parse_str("token-case = fatal").map(|_| ()),
+            Err("'token-case' has an invalid severity 'fatal' (must be off, info, warning or error)".to_string())
+        );
+        assert_eq!(
+            LintConfig::parse_str("no-such-rule = off").map(|_| ()),
+            Err("'no-such-rule' is not a lint rule".to_string())
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// `true`, `yes`, `on` or `1`, and `false`, `no`, `off` or `0`, case-insensitive
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// decimal, `0x` hexadecimal, `0o` or leading `0` octal, or `0b` binary
+pub fn parse_integer(value: &str) -> Option<u64> {
+    let lowercase = value.to_ascii_lowercase();
+    let (digits, radix) = if let Some(digits) = lowercase.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = lowercase.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = lowercase.strip_prefix("0b") {
+        (digits, 2)
+    } else if lowercase.len() > 1 && lowercase.starts_with("0") {
+        (&lowercase[1..], 8)
+    } else {
+        (lowercase.as_str(), 10)
+    };
+    // from_str_radix accepts a sign
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u64::from_str_radix(digits, radix).ok()
+}
+
+// number followed by a unit, or just a number in the base unit
+fn parse_with_unit(value: &str, units: &[(&str, u64)]) -> Option<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    if number.is_empty() {
+        return None;
+    }
+    let unit = unit.trim().to_ascii_lowercase();
+    let &(_, multiplier) = units.iter().find(|(name, _)| *name == unit)?;
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+const KIB: u64 = 1 << 10;
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+const TIB: u64 = 1 << 40;
+
+// bytes of `64K`, `1G` or `512` with binary units K, M, G and T, optionally followed by `B` or `iB`
+pub fn parse_size(value: &str) -> Option<u64> {
+    parse_with_unit(
+        value,
+        &[
+            ("", 1),
+            ("b", 1),
+            ("k", KIB),
+            ("kb", KIB),
+            ("kib", KIB),
+            ("m", MIB),
+            ("mb", MIB),
+            ("mib", MIB),
+            ("g", GIB),
+            ("gb", GIB),
+            ("gib", GIB),
+            ("t", TIB),
+            ("tb", TIB),
+            ("tib", TIB),
+        ],
+    )
+}
+
+// seconds of `30s`, `5m`, `2h`, `1d` or `30`
+pub fn parse_duration(value: &str) -> Option<u64> {
+    parse_with_unit(
+        value,
+        &[
+            ("", 1),
+            ("s", 1),
+            ("m", 60),
+            ("h", 60 * 60),
+            ("d", 24 * 60 * 60),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bool() {
+        assert_eq!(parse_bool("Yes"), Some(true));
+        assert_eq!(parse_bool("on"), Some(true));
+        assert_eq!(parse_bool("1"), Some(true));
+        assert_eq!(parse_bool("OFF"), Some(false));
+        assert_eq!(parse_bool("no"), Some(false));
+        assert_eq!(parse_bool("0"), Some(false));
+        assert_eq!(parse_bool("maybe"), None);
+    }
+
+    #[test]
+    fn integer() {
+        assert_eq!(parse_integer("0x1f"), Some(31));
+        assert_eq!(parse_integer("0X1F"), Some(31));
+        assert_eq!(parse_integer("0755"), Some(493));
+        assert_eq!(parse_integer("0o755"), Some(493));
+        assert_eq!(parse_integer("0b101"), Some(5));
+        assert_eq!(parse_integer("0"), Some(0));
+        assert_eq!(parse_integer("1024"), Some(1024));
+        assert_eq!(parse_integer("089"), None);
+        assert_eq!(parse_integer("0x"), None);
+        assert_eq!(parse_integer("0x+1"), None);
+        assert_eq!(parse_integer("-1"), None);
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64K"), Some(65536));
+        assert_eq!(parse_size("64kb"), Some(65536));
+        assert_eq!(parse_size("1 GiB"), Some(1 << 30));
+        assert_eq!(parse_size("2T"), Some(2 << 40));
+        assert_eq!(parse_size("16777216T"), None);
+        assert_eq!(parse_size("1.5G"), None);
+        assert_eq!(parse_size("K"), None);
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("30"), Some(30));
+        assert_eq!(parse_duration("30s"), Some(30));
+        assert_eq!(parse_duration("5m"), Some(300));
+        assert_eq!(parse_duration("2h"), Some(7200));
+        assert_eq!(parse_duration("1D"), Some(86400));
+        assert_eq!(parse_duration("5w"), None);
+    }
+}
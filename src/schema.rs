@@ -1,96 +1,855 @@
-use std::collections::HashMap;
+use crate::{
+    annotation::{self, Annotations},
+    constraint::{self, Constraint},
+    diagnostic::{Diagnostic, ParseError, ParseErrorKind, Severity},
+    hashmap::Origin,
+    source::{parse_include, physical_lines, IncludeChain, Sources},
+    sysctl::strip_inline_comment,
+    typed::convert_value,
+};
+use std::{collections::HashMap, path::Path};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SchemaType {
     String(),
     Bool(),
     Integer(),
+    Float(),
+    // bytes with a binary unit such as `64K`
+    Size(),
+    // seconds with a unit such as `5m`
+    Duration(),
+    // bool with aliases such as `yes`, or integer with a radix prefix such as `0x1f`
+    Normalized(Box<SchemaType>),
+    // items separated by `,`, or by whitespace if no `,`
+    List(Box<SchemaType>),
+    // submap whose literal values at any depth are of the type, for tokens not known ahead
+    Map(Box<SchemaType>),
+    // submap of any literal values
+    Object(),
+    // type of a token which may not be set
+    Optional(Box<SchemaType>),
 }
 
-pub type SchemaHashMap<'a> = HashMap<&'a str, SchemaType>;
+impl SchemaType {
+    // `string`, `bool`, `integer`, `float`, `size`, `duration`, `normalized(bool|integer)`,
+    // `list(type)` of any of them, `map(type)` of any of them or list, `object`, or
+    // `optional(type)` of any of them
+    pub fn parse(schema_type: &str) -> Option<SchemaType> {
+        match schema_type {
+            "string" => Some(SchemaType::String()),
+            "bool" => Some(SchemaType::Bool()),
+            "integer" => Some(SchemaType::Integer()),
+            "float" => Some(SchemaType::Float()),
+            "size" => Some(SchemaType::Size()),
+            "duration" => Some(SchemaType::Duration()),
+            "object" => Some(SchemaType::Object()),
+            schema_type => {
+                if let Some(normalized) = schema_type
+                    .strip_prefix("normalized(")
+                    .and_then(|normalized| normalized.strip_suffix(")"))
+                {
+                    return match SchemaType::parse(normalized.trim())? {
+                        normalized @ (SchemaType::Bool() | SchemaType::Integer()) => {
+                            Some(SchemaType::Normalized(Box::new(normalized)))
+                        }
+                        _ => None,
+                    };
+                }
+                if let Some(optional) = schema_type
+                    .strip_prefix("optional(")
+                    .and_then(|optional| optional.strip_suffix(")"))
+                {
+                    return match SchemaType::parse(optional.trim())? {
+                        SchemaType::Optional(_) => None,
+                        optional => Some(SchemaType::Optional(Box::new(optional))),
+                    };
+                }
+                if let Some(item) = schema_type
+                    .strip_prefix("map(")
+                    .and_then(|item| item.strip_suffix(")"))
+                {
+                    return match SchemaType::parse(item.trim())? {
+                        SchemaType::Map(_) | SchemaType::Object() | SchemaType::Optional(_) => None,
+                        item => Some(SchemaType::Map(Box::new(item))),
+                    };
+                }
+                let item = schema_type.strip_prefix("list(")?.strip_suffix(")")?;
+                match SchemaType::parse(item.trim())? {
+                    SchemaType::List(_)
+                    | SchemaType::Map(_)
+                    | SchemaType::Object()
+                    | SchemaType::Optional(_) => None,
+                    item => Some(SchemaType::List(Box::new(item))),
+                }
+            }
+        }
+    }
+    // type without `optional(...)`
+    pub fn base(&self) -> &SchemaType {
+        match self {
+            SchemaType::Optional(optional) => optional,
+            schema_type => schema_type,
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaType::String() => write!(f, "string"),
+            SchemaType::Bool() => write!(f, "bool"),
+            SchemaType::Integer() => write!(f, "integer"),
+            SchemaType::Float() => write!(f, "float"),
+            SchemaType::Size() => write!(f, "size"),
+            SchemaType::Duration() => write!(f, "duration"),
+            SchemaType::Normalized(normalized) => write!(f, "normalized({})", normalized),
+            SchemaType::List(item) => write!(f, "list({})", item),
+            SchemaType::Map(item) => write!(f, "map({})", item),
+            SchemaType::Object() => write!(f, "object"),
+            SchemaType::Optional(optional) => write!(f, "optional({})", optional),
+        }
+    }
+}
+
+// tree of schema types mirroring SysctlParameterHashMap, where a token may have both a type and
+// children such as `log => string` and `log.file => string`
+#[derive(Debug, Clone, Default)]
+pub struct SchemaHashMap<'a> {
+    // type of the token at this node, None for the root or a token only with children
+    pub schema_type: Option<SchemaType>,
+    pub items: HashMap<&'a str, SchemaHashMap<'a>>,
+    // constraints between tokens, only in the root
+    pub constraints: Vec<Constraint>,
+    // where the type is declared, which is not compared
+    pub origin: Option<Origin>,
+    // warnings from parsing such as a changed type, only in the root and not compared
+    pub warnings: Vec<Diagnostic>,
+    // metadata of the token such as its description, which is not compared
+    pub annotations: Annotations,
+}
+impl PartialEq for SchemaHashMap<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.schema_type == other.schema_type
+            && self.items == other.items
+            && self.constraints == other.constraints
+    }
+}
+impl<'a> SchemaHashMap<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn node(&self, path: &[&str]) -> Option<&SchemaHashMap<'a>> {
+        match path {
+            [] => Some(self),
+            [token, path @ ..] => self.items.get(*token)?.node(path),
+        }
+    }
+    // where the type of a dotted token is declared
+    pub fn origin(&self, token: &str) -> Option<&Origin> {
+        self.node(&token.split(".").collect::<Vec<&str>>())?
+            .origin
+            .as_ref()
+    }
+    // annotations of a dotted token, or None if it is not declared
+    pub fn annotations(&self, token: &str) -> Option<&Annotations> {
+        let node = self.node(&token.split(".").collect::<Vec<&str>>())?;
+        node.schema_type.as_ref().map(|_| &node.annotations)
+    }
+    // set annotations of a declared token
+    pub fn annotate(&mut self, path: &[&str], annotations: Annotations) {
+        match path {
+            [] => self.annotations = annotations,
+            [token, path @ ..] => {
+                if let Some(child) = self.items.get_mut(*token) {
+                    child.annotate(path, annotations);
+                }
+            }
+        }
+    }
+    // type of a dotted token such as `log.limit`
+    pub fn get(&self, token: &str) -> Option<&SchemaType> {
+        self.get_path(&token.split(".").collect::<Vec<&str>>())
+    }
+    pub fn get_path(&self, path: &[&str]) -> Option<&SchemaType> {
+        match path {
+            [] => self.schema_type.as_ref(),
+            [token, path @ ..] => self.items.get(*token)?.get_path(path),
+        }
+    }
+    pub fn insert(&mut self, path: &[&'a str], schema_type: SchemaType) {
+        self.insert_with_origin(path, schema_type, None)
+    }
+    pub fn insert_with_origin(
+        &mut self,
+        path: &[&'a str],
+        schema_type: SchemaType,
+        origin: Option<Origin>,
+    ) {
+        match path {
+            [] => {
+                self.schema_type = Some(schema_type);
+                self.origin = origin;
+            }
+            [token, path @ ..] => {
+                self.items
+                    .entry(token)
+                    .or_default()
+                    .insert_with_origin(path, schema_type, origin)
+            }
+        }
+    }
+    // type of a literal value of a dotted token, either its own or the item type of the nearest
+    // `map(type)` above it
+    pub fn resolve(&self, token: &str) -> Option<&SchemaType> {
+        self.resolve_path(&token.split(".").collect::<Vec<&str>>())
+    }
+    pub fn resolve_path(&self, path: &[&str]) -> Option<&SchemaType> {
+        match (path, self.schema_type.as_ref().map(SchemaType::base)) {
+            ([], Some(SchemaType::Map(_) | SchemaType::Object())) => None,
+            ([], _) => self.schema_type.as_ref(),
+            ([token, path @ ..], schema_type) => {
+                let resolved = self
+                    .items
+                    .get(*token)
+                    .and_then(|child| child.resolve_path(path));
+                match (resolved, schema_type) {
+                    (None, Some(SchemaType::Map(item))) => Some(item),
+                    (resolved, _) => resolved,
+                }
+            }
+        }
+    }
+    // all types with their dotted tokens, sorted by token
+    pub fn entries(&self) -> Vec<(String, &SchemaType)> {
+        self.nodes()
+            .into_iter()
+            .filter_map(|(token, node)| Some((token, node.schema_type.as_ref()?)))
+            .collect()
+    }
+    // all nodes with a type and their dotted tokens, sorted by token
+    pub(crate) fn nodes(&self) -> Vec<(String, &SchemaHashMap<'a>)> {
+        let mut nodes = vec![];
+        for (token, child) in &self.items {
+            if child.schema_type.is_some() {
+                nodes.push((token.to_string(), child));
+            }
+            for (path, node) in child.nodes() {
+                nodes.push((format!("{}.{}", token, path), node));
+            }
+        }
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        nodes
+    }
+}
+// from dotted tokens and their types
+impl<'a, const N: usize> From<[(&'a str, SchemaType); N]> for SchemaHashMap<'a> {
+    fn from(entries: [(&'a str, SchemaType); N]) -> Self {
+        let mut map = SchemaHashMap::new();
+        for (token, schema_type) in entries {
+            map.insert(&token.split(".").collect::<Vec<&str>>(), schema_type);
+        }
+        map
+    }
+}
+
+pub struct ParseOptions {
+    // strip comments introduced by ` #` or ` ;` after the schema type
+    pub inline_comments: bool,
+    // resolve `include path` directives, relative to the including file
+    pub include: bool,
+    pub max_include_depth: usize,
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            inline_comments: false,
+            include: false,
+            max_include_depth: 8,
+        }
+    }
+}
+
+// how a file declares a token which is already declared
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    // the type is replaced with a warning if it changes, which is the default of each file
+    Override,
+    // the token must not be declared yet
+    Extend,
+}
 
 struct SchemaParameter<'a> {
     path: &'a str,
+    // schema type as written
+    raw_type: &'a str,
     schema_type: SchemaType,
 }
 
-fn parse_token<'a>(path: &'a str, schema_type: &'a str) -> Result<SchemaParameter<'a>, String> {
-    let path = path.trim();
-    let schema_type = schema_type.trim();
+enum SchemaLine<'a> {
+    Entry(SchemaParameter<'a>),
+    // `token {` opening a block whose tokens are relative to token
+    Open(&'a str),
+    // `}` closing the innermost block
+    Close,
+    // `constraint <rule>` with tokens relative to the block
+    Constraint(&'a str),
+    // `include path` with tokens relative to the block
+    Include,
+    // `extend` or `override` for the following entries of the file
+    Mode(Mode),
+    // `# @name` comment annotating the following entry
+    Annotation(&'a str),
+}
+
+// token of an entry or a block, which is ignored if it begins with a `-`
+fn check_token(path: &str) -> Result<(), ParseError> {
     // invalid token begins or ends with a `.` or has continuous `.`
     if path.starts_with(".") || path.ends_with(".") || path.contains("..") {
-        Err(format!(
-            "Token '{}' has an invalid hierarchical structure",
-            path
+        Err(ParseError::new(
+            ParseErrorKind::InvalidToken,
+            format!("Token '{}' has an invalid hierarchical structure", path),
         ))
-    // valid token
     } else {
-        match schema_type {
-            "string" => Ok(SchemaParameter {
-                path,
-                schema_type: SchemaType::String(),
-            }),
-            "bool" => Ok(SchemaParameter {
-                path,
-                schema_type: SchemaType::Bool(),
-            }),
-            "integer" => Ok(SchemaParameter {
-                path,
-                schema_type: SchemaType::Integer(),
-            }),
-            _ => Err(format!(
-                "'{}' has an invalid schema type '{}' (must be string, bool or integer)",
-                path, schema_type
-            )),
-        }
-    }
-}
-
-fn parse_line<'a>(line: &'a str) -> Result<Option<SchemaParameter<'a>>, String> {
+        Ok(())
+    }
+}
+
+fn parse_token<'a>(path: &'a str, schema_type: &'a str) -> Result<SchemaParameter<'a>, ParseError> {
+    let path = path.trim();
+    let raw_type = schema_type.trim();
+    check_token(path)?;
+    match SchemaType::parse(raw_type) {
+        Some(schema_type) => Ok(SchemaParameter {
+            path,
+            raw_type,
+            schema_type,
+        }),
+        None => Err(ParseError::new(
+            ParseErrorKind::InvalidSchemaType,
+            format!(
+                "'{}' has an invalid schema type '{}' (must be string, bool, integer, float, size, duration, normalized(type), list(type), map(type), object or optional(type))",
+                path, raw_type
+            ),
+        )),
+    }
+}
+
+fn parse_line<'a>(
+    line: &'a str,
+    options: &ParseOptions,
+) -> Result<Option<SchemaLine<'a>>, ParseError> {
     let line = line.trim();
+    let block = match options.inline_comments {
+        true => strip_inline_comment(line).trim_end(),
+        false => line,
+    };
     // multiple lines
     if line.contains("\n") || line.contains("\r") {
-        Err(format!("'{}' is not a single line", line))
-    }
-    // empty, comment, or invalid token line
-    else if line.len() == 0
-        || line.starts_with("#")
-        || line.starts_with(";")
-        || line.starts_with("-")
-    {
+        Err(ParseError::new(
+            ParseErrorKind::InvalidSyntax,
+            format!("'{}' is not a single line", line),
+        ))
+    }
+    // annotation line
+    else if let Some(comment) = annotation::comment(line) {
+        Ok(Some(SchemaLine::Annotation(comment)))
+    // empty or comment line
+    } else if line.is_empty() || line.starts_with("#") || line.starts_with(";") {
+        Ok(None)
+    // include directive
+    } else if options.include && parse_include(block).is_some() {
+        Ok(Some(SchemaLine::Include))
+    // mode line
+    } else if block == "extend" {
+        Ok(Some(SchemaLine::Mode(Mode::Extend)))
+    } else if block == "override" {
+        Ok(Some(SchemaLine::Mode(Mode::Override)))
+    // constraint line
+    } else if !line.contains("=>") && block.starts_with("constraint ") {
+        Ok(Some(SchemaLine::Constraint(&block["constraint ".len()..])))
+    // block closing line
+    } else if block == "}" {
+        Ok(Some(SchemaLine::Close))
+    // block opening line
+    } else if !line.contains("=>") && block.ends_with("{") {
+        let path = block.strip_suffix("{").unwrap().trim();
+        if path.is_empty() {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidSyntax,
+                format!("'{}' has no token before `{{`", line),
+            ));
+        }
+        check_token(path.strip_prefix("-").unwrap_or(path))?;
+        Ok(Some(SchemaLine::Open(path)))
+    // invalid token line
+    } else if line.starts_with("-") {
         Ok(None)
     // invalid syntax line
     } else if !line.contains("=>") {
-        Err(format!(
-            "'{}' is not in format `token => schema_type`",
-            line
+        Err(ParseError::new(
+            ParseErrorKind::InvalidSyntax,
+            format!("'{}' is not in format `token => schema_type`", line),
         ))
     // valid syntax line
     } else {
-        let mut parameter = line.splitn(2, "=>");
-        let token = parameter.next().unwrap();
-        let value = parameter.next().unwrap();
+        let (token, value) = line.split_once("=>").unwrap();
+        let value = if options.inline_comments {
+            strip_inline_comment(value)
+        } else {
+            value
+        };
         match parse_token(token, value) {
-            Ok(parameter) => Ok(Some(parameter)),
+            Ok(parameter) => Ok(Some(SchemaLine::Entry(parameter))),
             Err(err) => Err(err),
         }
     }
 }
 
 pub fn parse_str<'a>(schema_conf: &'a str) -> Result<SchemaHashMap<'a>, String> {
-    let mut parameter = SchemaHashMap::new();
-    for line in schema_conf.lines() {
-        let parsed = parse_line(line)?;
-        if let Some(parsed) = parsed {
-            parameter.insert(&parsed.path, parsed.schema_type);
+    parse_str_with(schema_conf, &ParseOptions::default())
+}
+
+pub fn parse_str_with<'a>(
+    schema_conf: &'a str,
+    options: &ParseOptions,
+) -> Result<SchemaHashMap<'a>, String> {
+    parse_str_diagnostic(schema_conf, options).map_err(|diagnostic| diagnostic.message.clone())
+}
+
+// same as parse_str_with, with the error located at its line
+pub fn parse_str_diagnostic<'a>(
+    schema_conf: &'a str,
+    options: &ParseOptions,
+) -> Result<SchemaHashMap<'a>, Box<Diagnostic>> {
+    let file = SourceView {
+        path: None,
+        content: schema_conf,
+        includes: None,
+    };
+    Parser::new(vec![file], options, true).parse()
+}
+
+// load a schema file and, if enabled, all files included from it
+pub fn load(path: &Path, options: &ParseOptions) -> Result<Sources, String> {
+    Sources::load(
+        path,
+        options.include.then_some(options.max_include_depth),
+        &physical_lines,
+    )
+}
+
+// load content already read such as from stdin, with includes relative to path
+pub fn load_content(
+    path: &Path,
+    content: String,
+    options: &ParseOptions,
+) -> Result<Sources, String> {
+    Sources::load_content(
+        path,
+        content,
+        options.include.then_some(options.max_include_depth),
+        &physical_lines,
+    )
+}
+
+pub fn parse_sources<'a>(
+    sources: &'a Sources,
+    options: &ParseOptions,
+) -> Result<SchemaHashMap<'a>, String> {
+    parse_sources_diagnostic(sources, options).map_err(|diagnostic| {
+        let mut err = format!(
+            "{} at {}:{}",
+            diagnostic.message,
+            diagnostic.file.unwrap_or_default(),
+            diagnostic.line
+        );
+        for note in diagnostic.notes {
+            err.push_str(&format!(", {}", note));
+        }
+        err
+    })
+}
+
+// same as parse_sources, with the error located at its file and line
+pub fn parse_sources_diagnostic<'a>(
+    sources: &'a Sources,
+    options: &ParseOptions,
+) -> Result<SchemaHashMap<'a>, Box<Diagnostic>> {
+    let files = sources
+        .files
+        .iter()
+        .map(|file| SourceView {
+            path: Some(file.path.display().to_string()),
+            content: &file.content,
+            includes: Some(&file.includes),
+        })
+        .collect();
+    Parser::new(files, options, true).parse()
+}
+
+// 1-based line declaring each dotted token, the last one if declared more than once
+pub fn declarations(schema_conf: &str, options: &ParseOptions) -> HashMap<String, usize> {
+    let file = SourceView {
+        path: None,
+        content: schema_conf,
+        includes: None,
+    };
+    // never fails if not strict
+    let schema = Parser::new(vec![file], options, false)
+        .parse()
+        .unwrap_or_default();
+    schema
+        .nodes()
+        .into_iter()
+        .filter_map(|(token, node)| Some((token, node.origin.as_ref()?.line)))
+        .collect()
+}
+
+struct SourceView<'a> {
+    // None for a string
+    path: Option<String>,
+    content: &'a str,
+    includes: Option<&'a HashMap<usize, Vec<usize>>>,
+}
+
+struct Block<'a> {
+    index: usize,
+    path: Vec<&'a str>,
+    // block token begins with a `-`
+    ignored: bool,
+}
+
+// file, line index and include chain of a line
+type Location = (usize, usize, IncludeChain);
+
+struct Parser<'a, 'o> {
+    files: Vec<SourceView<'a>>,
+    options: &'o ParseOptions,
+    // fail on an invalid line instead of skipping it
+    strict: bool,
+    schema: SchemaHashMap<'a>,
+    constraints: Vec<(Location, Constraint)>,
+    // replacements of deprecated tokens
+    replacements: Vec<(Location, String)>,
+}
+
+impl<'a, 'o> Parser<'a, 'o> {
+    fn new(files: Vec<SourceView<'a>>, options: &'o ParseOptions, strict: bool) -> Self {
+        Parser {
+            files,
+            options,
+            strict,
+            schema: SchemaHashMap::new(),
+            constraints: vec![],
+            replacements: vec![],
+        }
+    }
+    fn location(&self, file: usize, index: usize) -> String {
+        match &self.files[file].path {
+            Some(path) => format!("{}:{}", path, index + 1),
+            None => format!("line {}", index + 1),
+        }
+    }
+    fn error(
+        &self,
+        file: usize,
+        index: usize,
+        chain: &[(usize, usize)],
+        err: ParseError,
+    ) -> Diagnostic {
+        let source = &self.files[file];
+        let mut diagnostic = Diagnostic::parse_error(source.content, index, "=>", err);
+        diagnostic.file = source.path.clone();
+        for &(file, line) in chain.iter().rev() {
+            diagnostic
+                .notes
+                .push(format!("included from {}", self.location(file, line)));
+        }
+        diagnostic
+    }
+    fn parse(mut self) -> Result<SchemaHashMap<'a>, Box<Diagnostic>> {
+        self.parse_file(0, &[], &mut vec![])?;
+        // constraints refer to declared tokens
+        for ((file, index, chain), constraint) in &self.constraints {
+            let undeclared = constraint
+                .tokens()
+                .into_iter()
+                .find(|token| self.schema.resolve(token).is_none());
+            if let (Some(token), true) = (undeclared, self.strict) {
+                return Err(Box::new(self.error(
+                    *file,
+                    *index,
+                    chain,
+                    ParseError::new(
+                        ParseErrorKind::InvalidToken,
+                        format!("Token '{}' of constraint is not declared", token),
+                    ),
+                )));
+            }
+        }
+        for ((file, index, chain), token) in &self.replacements {
+            if self.schema.get(token).is_none() && self.strict {
+                return Err(Box::new(self.error(
+                    *file,
+                    *index,
+                    chain,
+                    ParseError::new(
+                        ParseErrorKind::InvalidToken,
+                        format!("Token '{}' of deprecation is not declared", token),
+                    ),
+                )));
+            }
+        }
+        self.schema.constraints = self
+            .constraints
+            .into_iter()
+            .map(|(_, constraint)| constraint)
+            .collect();
+        Ok(self.schema)
+    }
+    fn parse_file(
+        &mut self,
+        file: usize,
+        prefix: &[&'a str],
+        chain: &mut IncludeChain,
+    ) -> Result<(), Box<Diagnostic>> {
+        let content = self.files[file].content;
+        let mut mode = Mode::Override;
+        let mut blocks: Vec<Block> = vec![];
+        // annotations for the next entry and the line index of the first one
+        let mut pending: Option<(usize, Annotations)> = None;
+        for (index, line) in content.lines().enumerate() {
+            let parsed = match parse_line(line, self.options) {
+                Ok(parsed) => parsed,
+                Err(err) if self.strict => {
+                    return Err(Box::new(self.error(file, index, chain, err)))
+                }
+                Err(_) => None,
+            };
+            let ignored = blocks.iter().any(|block| block.ignored);
+            let mut path = prefix.to_vec();
+            path.extend(blocks.iter().flat_map(|block| block.path.clone()));
+            match &parsed {
+                // ignored entry or block drops the annotations
+                _ if line.trim().starts_with("-") => pending = None,
+                Some(SchemaLine::Annotation(_)) | Some(SchemaLine::Entry(_)) | None => {}
+                _ => {
+                    if let Some((start, _)) = pending.take() {
+                        self.warn_unattached(file, start, chain);
+                    }
+                }
+            }
+            match parsed {
+                Some(SchemaLine::Annotation(comment)) if !ignored => {
+                    let (_, annotations) = pending.get_or_insert((index, Annotations::default()));
+                    if let Err(err) = annotation::parse(comment, &path, annotations) {
+                        if self.strict {
+                            let err = ParseError::new(ParseErrorKind::InvalidSyntax, err);
+                            return Err(Box::new(self.error(file, index, chain, err)));
+                        }
+                    }
+                }
+                Some(SchemaLine::Entry(parameter)) if !ignored => {
+                    path.extend(parameter.path.split("."));
+                    let annotations = pending.take().map(|(_, annotations)| annotations);
+                    self.declare(file, index, chain, mode, &path, parameter)?;
+                    self.annotate(file, index, chain, &path, annotations.unwrap_or_default())?;
+                }
+                Some(SchemaLine::Entry(_)) => pending = None,
+                Some(SchemaLine::Constraint(rule)) if !ignored => {
+                    match constraint::parse(rule, &path) {
+                        Ok(constraint) => self
+                            .constraints
+                            .push(((file, index, chain.clone()), constraint)),
+                        Err(err) if self.strict => {
+                            let err = ParseError::new(ParseErrorKind::InvalidSyntax, err);
+                            return Err(Box::new(self.error(file, index, chain, err)));
+                        }
+                        Err(_) => {}
+                    }
+                }
+                Some(SchemaLine::Include) if !ignored => {
+                    let includes = self.files[file].includes;
+                    match includes.and_then(|includes| includes.get(&index)) {
+                        Some(included) => {
+                            chain.push((file, index));
+                            for &included in included {
+                                self.parse_file(included, &path, chain)?;
+                            }
+                            chain.pop();
+                        }
+                        None if self.strict => {
+                            let err = ParseError::new(
+                                ParseErrorKind::InvalidSyntax,
+                                format!(
+                                    "'{}' is not resolved, which requires loading the schema by schema::load",
+                                    line.trim()
+                                ),
+                            );
+                            return Err(Box::new(self.error(file, index, chain, err)));
+                        }
+                        None => {}
+                    }
+                }
+                Some(SchemaLine::Mode(new_mode)) => mode = new_mode,
+                Some(SchemaLine::Open(path)) => blocks.push(Block {
+                    index,
+                    path: path.split(".").collect(),
+                    ignored: path.starts_with("-"),
+                }),
+                Some(SchemaLine::Close) => match blocks.pop() {
+                    None if self.strict => {
+                        let err = ParseError::new(
+                            ParseErrorKind::InvalidSyntax,
+                            "'}' has no block to close".to_string(),
+                        );
+                        return Err(Box::new(self.error(file, index, chain, err)));
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        if let Some((start, _)) = pending {
+            self.warn_unattached(file, start, chain);
+        }
+        match blocks.last() {
+            Some(block) if self.strict => {
+                let err = ParseError::new(
+                    ParseErrorKind::InvalidSyntax,
+                    format!("Block '{}' is not closed", block.path.join(".")),
+                );
+                Err(Box::new(self.error(file, block.index, chain, err)))
+            }
+            _ => Ok(()),
+        }
+    }
+    // annotations not followed by an entry are left as comments with a warning
+    fn warn_unattached(&mut self, file: usize, index: usize, chain: &[(usize, usize)]) {
+        let err = ParseError::new(
+            ParseErrorKind::InvalidSyntax,
+            "Annotations are not followed by `token => schema_type`".to_string(),
+        );
+        let warning = Diagnostic {
+            code: "unattached-annotation".to_string(),
+            severity: Severity::Warning,
+            ..self.error(file, index, chain, err)
+        };
+        self.schema.warnings.push(warning);
+    }
+    // set annotations of a declared entry, which are kept from a previous declaration if it has
+    // none, with its default checked against its type
+    fn annotate(
+        &mut self,
+        file: usize,
+        index: usize,
+        chain: &[(usize, usize)],
+        path: &[&'a str],
+        annotations: Annotations,
+    ) -> Result<(), Box<Diagnostic>> {
+        let token = path.join(".");
+        if annotations.is_empty() {
+            return Ok(());
+        }
+        let schema_type = self.schema.get_path(path).unwrap();
+        if let (Some(default), true) = (&annotations.default, self.strict) {
+            if convert_value(&token, default, schema_type).is_err() {
+                let err = ParseError::new(
+                    ParseErrorKind::InvalidValue,
+                    format!(
+                        "'{}' has an invalid default '{}' for its type {}",
+                        token, default, schema_type
+                    ),
+                );
+                return Err(Box::new(self.error(file, index, chain, err)));
+            }
+        }
+        if let Some(replacement) = &annotations.replacement {
+            self.replacements
+                .push(((file, index, chain.to_vec()), replacement.clone()));
         }
+        self.schema.annotate(path, annotations);
+        Ok(())
+    }
+    // insert the type of an entry, failing if already declared in extend mode or warning if its
+    // type changes
+    fn declare(
+        &mut self,
+        file: usize,
+        index: usize,
+        chain: &[(usize, usize)],
+        mode: Mode,
+        path: &[&'a str],
+        parameter: SchemaParameter<'a>,
+    ) -> Result<(), Box<Diagnostic>> {
+        let token = path.join(".");
+        let previous = self.schema.node(path).and_then(|node| {
+            let schema_type = node.schema_type.as_ref()?;
+            let declared = match &node.origin {
+                Some(Origin {
+                    file: Some(file),
+                    line,
+                    ..
+                }) => format!(" at {}:{}", file, line),
+                Some(origin) => format!(" at line {}", origin.line),
+                None => String::new(),
+            };
+            Some((schema_type.clone(), declared))
+        });
+        match previous {
+            Some((_, declared)) if mode == Mode::Extend && self.strict => {
+                let err = ParseError::new(
+                    ParseErrorKind::InvalidToken,
+                    format!(
+                        "Token '{}' is already declared{} (use override to change it)",
+                        token, declared
+                    ),
+                );
+                return Err(Box::new(self.error(file, index, chain, err)));
+            }
+            Some((previous, declared)) if previous != parameter.schema_type => {
+                let err = ParseError::new(
+                    ParseErrorKind::InvalidSchemaType,
+                    format!(
+                        "'{}' overrides its type {} with {}",
+                        token, previous, parameter.schema_type
+                    ),
+                );
+                let mut warning = Diagnostic {
+                    code: "type-override".to_string(),
+                    severity: Severity::Warning,
+                    ..self.error(file, index, chain, err)
+                };
+                if !declared.is_empty() {
+                    warning
+                        .notes
+                        .insert(0, format!("previously declared{}", declared));
+                }
+                self.schema.warnings.push(warning);
+            }
+            _ => {}
+        }
+        let raw = self.files[file].content.lines().nth(index).unwrap_or("");
+        let key = raw.len() - raw.trim_start().len();
+        let after = raw.find("=>").map_or(0, |separator| separator + 2);
+        let value = after + raw[after..].len() - raw[after..].trim_start().len();
+        let origin = Origin {
+            file: self.files[file].path.clone(),
+            line: index + 1,
+            key: key..key + parameter.path.len(),
+            value: value..value + parameter.raw_type.len(),
+        };
+        self.schema
+            .insert_with_origin(path, parameter.schema_type, Some(origin));
+        Ok(())
     }
-    Ok(parameter)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::source::write_files;
 
     #[test]
     fn sample() {
@@ -152,6 +911,32 @@ endpoint => string
         );
     }
 
+    #[test]
+    fn inline_comments() {
+        let options = ParseOptions {
+            inline_comments: true,
+            ..Default::default()
+        };
+        let result = parse_str_with(
+            "endpoint => string  # host:port
+debug => bool ; for development",
+            &options,
+        );
+        assert_eq!(
+            result.unwrap(),
+            SchemaHashMap::from([
+                ("endpoint", SchemaType::String()),
+                ("debug", SchemaType::Bool()),
+            ])
+        );
+
+        let result = parse_str("endpoint => string  # host:port");
+        assert_eq!(
+            result,
+            Err("'endpoint' has an invalid schema type 'string  # host:port' (must be string, bool, integer, float, size, duration, normalized(type), list(type), map(type), object or optional(type))".to_string())
+        );
+    }
+
     #[test]
     fn invalid_token_begins_with_hyphen() {
         let result = parse_str(
@@ -205,7 +990,462 @@ endpoint => string
         let result = parse_str("end.point => localhost:3000");
         assert_eq!(
             result,
-            Err("'end.point' has an invalid schema type 'localhost:3000' (must be string, bool or integer)".to_string())
+            Err("'end.point' has an invalid schema type 'localhost:3000' (must be string, bool, integer, float, size, duration, normalized(type), list(type), map(type), object or optional(type))".to_string())
+        );
+    }
+
+    #[test]
+    fn declarations() {
+        let result = super::declarations(
+            "# schema
+endpoint => string
+invalid
+log.limit => integer # limit
+endpoint => bool",
+            &ParseOptions {
+                inline_comments: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            result,
+            HashMap::from([("endpoint".to_string(), 5), ("log.limit".to_string(), 4)])
+        );
+    }
+
+    #[test]
+    fn types() {
+        let result = parse_str(
+            "log.ratio => float
+net.ports => list(integer)
+net.hosts => list( string )",
+        );
+        assert_eq!(
+            result.unwrap(),
+            SchemaHashMap::from([
+                ("log.ratio", SchemaType::Float()),
+                (
+                    "net.ports",
+                    SchemaType::List(Box::new(SchemaType::Integer()))
+                ),
+                (
+                    "net.hosts",
+                    SchemaType::List(Box::new(SchemaType::String()))
+                ),
+            ])
+        );
+        assert_eq!(
+            SchemaType::List(Box::new(SchemaType::Bool())).to_string(),
+            "list(bool)"
+        );
+        assert_eq!(
+            parse_str("log.limit => size\nlog.rotate => duration\ndebug => normalized( bool )")
+                .unwrap(),
+            SchemaHashMap::from([
+                ("log.limit", SchemaType::Size()),
+                ("log.rotate", SchemaType::Duration()),
+                (
+                    "debug",
+                    SchemaType::Normalized(Box::new(SchemaType::Bool()))
+                ),
+            ])
+        );
+        assert_eq!(
+            SchemaType::List(Box::new(SchemaType::Normalized(Box::new(
+                SchemaType::Integer()
+            ))))
+            .to_string(),
+            "list(normalized(integer))"
+        );
+        assert_eq!(
+            parse_str("endpoint => normalized(string)"),
+            Err("'endpoint' has an invalid schema type 'normalized(string)' (must be string, bool, integer, float, size, duration, normalized(type), list(type), map(type), object or optional(type))".to_string())
+        );
+        assert_eq!(
+            parse_str("net.conf => map(list(integer))\nlog => object").unwrap(),
+            SchemaHashMap::from([
+                (
+                    "net.conf",
+                    SchemaType::Map(Box::new(SchemaType::List(Box::new(SchemaType::Integer()))))
+                ),
+                ("log", SchemaType::Object()),
+            ])
+        );
+        assert!(parse_str("net.conf => map(object)").is_err());
+        assert!(parse_str("net.conf => map(map(integer))").is_err());
+        assert!(parse_str("net.ports => list(map(integer))").is_err());
+        let schema = parse_str("net.conf => map(integer)\nnet.conf.lo.name => string").unwrap();
+        assert_eq!(
+            schema.resolve("net.conf.eth0.mtu"),
+            Some(&SchemaType::Integer())
+        );
+        assert_eq!(
+            schema.resolve("net.conf.lo.name"),
+            Some(&SchemaType::String())
+        );
+        assert_eq!(schema.resolve("net.conf"), None);
+        assert_eq!(schema.resolve("net"), None);
+        assert_eq!(
+            parse_str("net.ports => list(list(integer))"),
+            Err("'net.ports' has an invalid schema type 'list(list(integer))' (must be string, bool, integer, float, size, duration, normalized(type), list(type), map(type), object or optional(type))".to_string())
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let result = parse_str(
+            "net.ipv4 {
+    tcp_syncookies => bool
+    conf.default {
+        rp_filter => integer
+        accept_redirects => bool
+    }
+}
+-net.ipv6 {
+    forwarding => bool
+}
+endpoint => string",
+        );
+        assert_eq!(
+            result.unwrap(),
+            SchemaHashMap::from([
+                ("net.ipv4.tcp_syncookies", SchemaType::Bool()),
+                ("net.ipv4.conf.default.rp_filter", SchemaType::Integer()),
+                ("net.ipv4.conf.default.accept_redirects", SchemaType::Bool()),
+                ("endpoint", SchemaType::String()),
+            ])
+        );
+
+        let result = parse_str_with(
+            "log { # log settings
+log => string
+file => string
+} ; end of log",
+            &ParseOptions {
+                inline_comments: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.get("log.log"), Some(&SchemaType::String()));
+        assert_eq!(result.get("log.file"), Some(&SchemaType::String()));
+        assert_eq!(result.get("log"), None);
+        assert_eq!(
+            result.entries(),
+            vec![
+                ("log.file".to_string(), &SchemaType::String()),
+                ("log.log".to_string(), &SchemaType::String()),
+            ]
+        );
+
+        let result = super::declarations(
+            "log {\n    file => string\n}\nlog.limit => integer",
+            &ParseOptions::default(),
+        );
+        assert_eq!(
+            result,
+            HashMap::from([("log.file".to_string(), 2), ("log.limit".to_string(), 4)])
+        );
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        assert_eq!(
+            parse_str("log {\n    file => string"),
+            Err("Block 'log' is not closed".to_string())
+        );
+        assert_eq!(
+            parse_str("log.file => string\n}"),
+            Err("'}' has no block to close".to_string())
+        );
+        assert_eq!(
+            parse_str("{\n}"),
+            Err("'{' has no token before `{`".to_string())
+        );
+        assert_eq!(
+            parse_str("log. {\n}"),
+            Err("Token 'log.' has an invalid hierarchical structure".to_string())
+        );
+        let result = parse_str_diagnostic("log {\n}\nnet {", &ParseOptions::default());
+        assert_eq!(result.unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn constraints() {
+        let result = parse_str(
+            "vm.dirty_ratio => integer
+vm.dirty_background_ratio => integer
+constraint vm.dirty_background_ratio < vm.dirty_ratio
+log {
+    enabled => bool
+    file => optional(string)
+    constraint enabled == true requires file
+}",
+        )
+        .unwrap();
+        assert_eq!(
+            result.get("log.file"),
+            Some(&SchemaType::Optional(Box::new(SchemaType::String())))
+        );
+        assert_eq!(
+            result
+                .constraints
+                .iter()
+                .map(|constraint| constraint.tokens())
+                .collect::<Vec<_>>(),
+            vec![
+                vec!["vm.dirty_background_ratio", "vm.dirty_ratio"],
+                vec!["log.enabled", "log.file"],
+            ]
+        );
+        assert_eq!(
+            result.constraints[1].to_string(),
+            "enabled == true requires file"
+        );
+
+        let result = parse_str_diagnostic(
+            "log.enabled => bool\nconstraint log.enabled requires log.file",
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            result.to_string(),
+            "2:1: error[invalid-token]: Token 'log.file' of constraint is not declared"
+        );
+        assert_eq!(
+            parse_str("log.limit => integer\nconstraint log.limit"),
+            Err(
+                "'log.limit' is not a comparison such as `token < token` in constraint".to_string()
+            )
+        );
+        // a token named constraint is still a token
+        assert_eq!(
+            parse_str("constraint => string").unwrap(),
+            SchemaHashMap::from([("constraint", SchemaType::String())])
+        );
+        assert!(parse_str("log => optional(optional(string))").is_err());
+        assert!(parse_str("log => list(optional(string))").is_err());
+    }
+
+    #[test]
+    fn error_codes() {
+        let code = |schema_conf: &str| {
+            parse_str_diagnostic(schema_conf, &ParseOptions::default())
+                .unwrap_err()
+                .code
+        };
+        assert_eq!(code("log..limit => size"), "invalid-token");
+        assert_eq!(code("log.limit => bytes"), "invalid-schema-type");
+        assert_eq!(code("# @default=64X\nlog.limit => size"), "invalid-value");
+        assert_eq!(code("log.limit"), "invalid-syntax");
+        assert_eq!(code("log {"), "invalid-syntax");
+    }
+
+    #[test]
+    fn annotations() {
+        let result = parse_str(
+            "# @desc Maximum size of a log file
+# @unit=bytes @since=5.10 @default=64K
+log.limit => size
+
+log {
+    # @deprecated(use=limit)
+    size => size
+    # not an annotation
+    - file => string
+}
+# @desc Endpoint
+-endpoint => string
+endpoint => string",
+        )
+        .unwrap();
+        assert_eq!(
+            result.annotations("log.limit"),
+            Some(&Annotations {
+                description: Some("Maximum size of a log file".to_string()),
+                unit: Some("bytes".to_string()),
+                since: Some("5.10".to_string()),
+                default: Some("64K".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            result.annotations("log.size"),
+            Some(&Annotations {
+                deprecated: true,
+                replacement: Some("log.limit".to_string()),
+                ..Default::default()
+            })
+        );
+        // annotations of an ignored entry are dropped
+        assert_eq!(
+            result.annotations("endpoint"),
+            Some(&Annotations::default())
+        );
+        assert_eq!(result.annotations("log"), None);
+
+        let result = parse_str("# @desc Log\nlog {\n    # @desc Limit\n}\n# @desc Size").unwrap();
+        assert_eq!(
+            result
+                .warnings
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "1:1: warning[unattached-annotation]: Annotations are not followed by `token => schema_type`",
+                "3:5: warning[unattached-annotation]: Annotations are not followed by `token => schema_type`",
+                "5:1: warning[unattached-annotation]: Annotations are not followed by `token => schema_type`",
+            ]
+        );
+        assert_eq!(
+            parse_str("# @default=64X\nlog.limit => size"),
+            Err("'log.limit' has an invalid default '64X' for its type size".to_string())
+        );
+        assert_eq!(
+            parse_str("# @deprecated(use=log.max)\nlog.limit => size"),
+            Err("Token 'log.max' of deprecation is not declared".to_string())
+        );
+        // unknown annotations are comments
+        let result = parse_str("# @todo\n# @author me\nlog.limit => size").unwrap();
+        assert!(result.warnings.is_empty());
+        assert_eq!(
+            result.annotations("log.limit"),
+            Some(&Annotations::default())
+        );
+    }
+
+    #[test]
+    fn include() {
+        let dir = write_files(
+            "include",
+            &[
+                (
+                    "app.schema",
+                    "endpoint => string
+include base.schema
+log {
+    include log.schema
+}",
+                ),
+                ("base.schema", "debug => bool\nendpoint => string"),
+                ("log.schema", "file => string\nconstraint file != \"\""),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let sources = load(&dir.join("app.schema"), &options).unwrap();
+        let result = parse_sources(&sources, &options).unwrap();
+        assert_eq!(
+            result.entries(),
+            vec![
+                ("debug".to_string(), &SchemaType::Bool()),
+                ("endpoint".to_string(), &SchemaType::String()),
+                ("log.file".to_string(), &SchemaType::String()),
+            ]
+        );
+        assert_eq!(result.constraints[0].tokens(), vec!["log.file"]);
+        let origin = result.origin("log.file").unwrap();
+        assert_eq!(
+            (origin.file.clone(), origin.line, origin.key.clone()),
+            (Some(dir.join("log.schema").display().to_string()), 1, 0..4)
+        );
+        assert_eq!(result.origin("endpoint").unwrap().line, 2);
+        assert!(result.warnings.is_empty());
+
+        // includes are not resolved without loading
+        assert_eq!(
+            parse_str_with("include base.schema", &options),
+            Err("'include base.schema' is not resolved, which requires loading the schema by schema::load".to_string())
+        );
+        assert_eq!(
+            parse_str("include base.schema"),
+            Err("'include base.schema' is not in format `token => schema_type`".to_string())
+        );
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = write_files(
+            "include_cycle",
+            &[
+                ("app.schema", "include log.schema"),
+                ("log.schema", "log.file => string\ninclude app.schema"),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let result = load(&dir.join("app.schema"), &options).map(|_| ());
+        assert_eq!(
+            result,
+            Err(format!(
+                "include cycle detected for '{}' at {}:2, included from {}:1",
+                dir.join("app.schema").display(),
+                dir.join("log.schema").display(),
+                dir.join("app.schema").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let dir = write_files(
+            "modes",
+            &[
+                ("base.schema", "log.limit => integer\nendpoint => string"),
+                (
+                    "override.schema",
+                    "include base.schema
+log.limit => size
+endpoint => string",
+                ),
+                (
+                    "extend.schema",
+                    "include base.schema
+extend
+debug => bool
+log.limit => size",
+                ),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let sources = load(&dir.join("override.schema"), &options).unwrap();
+        let result = parse_sources(&sources, &options).unwrap();
+        assert_eq!(
+            result,
+            SchemaHashMap::from([
+                ("log.limit", SchemaType::Size()),
+                ("endpoint", SchemaType::String()),
+            ])
+        );
+        // the same type is redeclared silently
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(
+            result.warnings[0].to_string(),
+            format!(
+                "{}:2:1: warning[type-override]: 'log.limit' overrides its type integer with size
+  note: previously declared at {}:1",
+                dir.join("override.schema").display(),
+                dir.join("base.schema").display()
+            )
+        );
+
+        let sources = load(&dir.join("extend.schema"), &options).unwrap();
+        assert_eq!(
+            parse_sources(&sources, &options),
+            Err(format!(
+                "Token 'log.limit' is already declared at {}:1 (use override to change it) at {}:4",
+                dir.join("base.schema").display(),
+                dir.join("extend.schema").display()
+            ))
         );
+        // extend is reset in each file and can be switched back
+        assert!(parse_str("log.limit => integer\nextend\noverride\nlog.limit => size").is_ok());
     }
 }
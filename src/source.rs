@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub content: String,
+    // include directives resolved to indices of included files, keyed by line index
+    pub includes: HashMap<usize, Vec<usize>>,
+}
+
+// a root file and all files included from it
+pub struct Sources {
+    pub files: Vec<SourceFile>,
+}
+
+// chain of (file index, line index) from the root file to the current include directive
+pub type IncludeChain = Vec<(usize, usize)>;
+
+// lines of content where include directives are looked up, each with the index of its first
+// physical line, such as logical lines joining continued lines
+pub type Lines<'f> = &'f dyn Fn(&str) -> Vec<(usize, String)>;
+
+pub fn physical_lines(content: &str) -> Vec<(usize, String)> {
+    content
+        .lines()
+        .map(|line| line.to_string())
+        .enumerate()
+        .collect()
+}
+
+pub fn parse_include(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let target = line.strip_prefix("include")?;
+    // `include = value` or `include foo = bar` is a valid token, not a directive
+    if !target.starts_with(char::is_whitespace) || target.contains("=") {
+        return None;
+    }
+    Some(target.trim())
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+    // (pattern index, name index) to resume from on mismatch after `*`
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn resolve_include(base: &Path, target: &str) -> Result<Vec<PathBuf>, String> {
+    let path = match base.parent() {
+        Some(parent) => parent.join(target),
+        None => PathBuf::from(target),
+    };
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    // literal path
+    if !file_name.contains(['*', '?']) {
+        return Ok(vec![path]);
+    }
+    // wildcard in the last component, expanded in sorted order
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return Err(format!("cannot open '{}': {}", dir.display(), err)),
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| wildcard_match(file_name, name))
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    Ok(paths)
+}
+
+impl Sources {
+    // load a file and, unless max_include_depth is None, all files included from it with
+    // directives looked up in its lines
+    pub fn load(
+        path: &Path,
+        max_include_depth: Option<usize>,
+        lines: Lines,
+    ) -> Result<Sources, String> {
+        let mut sources = Sources { files: vec![] };
+        let mut loaded = HashMap::new();
+        sources.load_file(
+            path,
+            None,
+            max_include_depth,
+            lines,
+            &mut vec![],
+            &mut loaded,
+        )?;
+        Ok(sources)
+    }
+    // same as load, but with the root file content already read such as from stdin
+    pub fn load_content(
+        path: &Path,
+        content: String,
+        max_include_depth: Option<usize>,
+        lines: Lines,
+    ) -> Result<Sources, String> {
+        let mut sources = Sources { files: vec![] };
+        let mut loaded = HashMap::new();
+        sources.load_file(
+            path,
+            Some(content),
+            max_include_depth,
+            lines,
+            &mut vec![],
+            &mut loaded,
+        )?;
+        Ok(sources)
+    }
+    pub fn location(&self, file: usize, line: usize) -> String {
+        format!("{}:{}", self.files[file].path.display(), line + 1)
+    }
+    // `at path:line, included from path:line, ...`
+    pub fn describe(&self, file: usize, line: usize, chain: &[(usize, usize)]) -> String {
+        let mut description = format!("at {}", self.location(file, line));
+        for &(file, line) in chain.iter().rev() {
+            description.push_str(&format!(", included from {}", self.location(file, line)));
+        }
+        description
+    }
+    fn load_file(
+        &mut self,
+        path: &Path,
+        content: Option<String>,
+        max_include_depth: Option<usize>,
+        lines: Lines,
+        chain: &mut IncludeChain,
+        loaded: &mut HashMap<PathBuf, usize>,
+    ) -> Result<usize, String> {
+        let describe_chain = |sources: &Sources, chain: &IncludeChain| match chain.last() {
+            Some(&(file, line)) => format!(
+                " {}",
+                sources.describe(file, line, &chain[..chain.len() - 1])
+            ),
+            None => String::new(),
+        };
+        let content = match content {
+            Some(content) => Ok(content),
+            None => fs::read_to_string(path),
+        }
+        .map_err(|err| {
+            format!(
+                "cannot open '{}': {}{}",
+                path.display(),
+                err,
+                describe_chain(self, chain)
+            )
+        })?;
+        let canonical = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        // included more than once but not recursively
+        if let Some(&index) = loaded.get(&canonical) {
+            if chain.iter().any(|&(file, _)| file == index) {
+                return Err(format!(
+                    "include cycle detected for '{}'{}",
+                    path.display(),
+                    describe_chain(self, chain)
+                ));
+            }
+            return Ok(index);
+        }
+        let index = self.files.len();
+        loaded.insert(canonical, index);
+        self.files.push(SourceFile {
+            path: path.to_path_buf(),
+            content,
+            includes: HashMap::new(),
+        });
+        let max_include_depth = match max_include_depth {
+            Some(max_include_depth) => max_include_depth,
+            None => return Ok(index),
+        };
+
+        let targets = lines(&self.files[index].content)
+            .into_iter()
+            .filter_map(|(line, content)| {
+                parse_include(&content).map(|target| (line, target.to_string()))
+            })
+            .collect::<Vec<(usize, String)>>();
+        for (line, target) in targets {
+            chain.push((index, line));
+            if chain.len() > max_include_depth {
+                return Err(format!(
+                    "include depth exceeds {}{}",
+                    max_include_depth,
+                    describe_chain(self, chain)
+                ));
+            }
+            let mut included = vec![];
+            for included_path in resolve_include(path, &target)
+                .map_err(|err| format!("{}{}", err, describe_chain(self, chain)))?
+            {
+                included.push(self.load_file(
+                    &included_path,
+                    None,
+                    Some(max_include_depth),
+                    lines,
+                    chain,
+                    loaded,
+                )?);
+            }
+            self.files[index].includes.insert(line, included);
+            chain.pop();
+        }
+        Ok(index)
+    }
+}
+
+// files written to a new directory under the temporary directory, unique per test and process
+#[cfg(test)]
+pub(crate) fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "rust-sysctl-loader-{}-{}-{}",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_directive() {
+        assert_eq!(parse_include("include net.conf"), Some("net.conf"));
+        assert_eq!(
+            parse_include("  include   conf.d/*.conf  "),
+            Some("conf.d/*.conf")
+        );
+        assert_eq!(parse_include("include = net.conf"), None);
+        assert_eq!(parse_include("include foo = bar"), None);
+        assert_eq!(parse_include("include.file = net.conf"), None);
+        assert_eq!(parse_include("included net.conf"), None);
+        assert_eq!(parse_include("include"), None);
+    }
+
+    #[test]
+    fn wildcard() {
+        assert!(wildcard_match("*.conf", "net.conf"));
+        assert!(wildcard_match("*.conf", ".conf"));
+        assert!(wildcard_match("net?.conf", "net1.conf"));
+        assert!(wildcard_match("*", "net.conf"));
+        assert!(wildcard_match("a*b*c", "aXXbYYbc"));
+        assert!(!wildcard_match("*.conf", "net.conf.bak"));
+        assert!(!wildcard_match("net?.conf", "net.conf"));
+    }
+}
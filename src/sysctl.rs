@@ -1,69 +1,572 @@
-use crate::hashmap::SysctlParameterHashMap;
+use crate::{
+    diagnostic::{Diagnostic, ParseError, ParseErrorKind},
+    hashmap::{Origin, SysctlParameterHashMap, SysctlParameterValue},
+    source::{IncludeChain, Sources},
+};
+use std::{borrow::Cow, io::BufRead, ops::Range, path::Path};
+
+#[derive(Clone)]
+pub struct ParseOptions {
+    // resolve `include path` directives, relative to the including file
+    pub include: bool,
+    pub max_include_depth: usize,
+    // unquote `"value"` and `'value'` with `\n`, `\t`, `\"`, `\'` and `\\` escapes
+    pub quoting: bool,
+    // strip comments introduced by ` #` or ` ;` after the value
+    pub inline_comments: bool,
+    // join physical lines ending in `\` into one logical line
+    pub line_continuation: bool,
+    // record the Origin of each literal value, queried by SysctlParameterHashMap::origin
+    pub origins: bool,
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            include: false,
+            max_include_depth: 8,
+            quoting: false,
+            inline_comments: false,
+            line_continuation: false,
+            origins: false,
+        }
+    }
+}
 
 struct SysctlParameter<'a> {
     path: Vec<&'a str>,
-    value: &'a str,
+    value: SysctlParameterValue<'a>,
 }
 
-fn parse_token<'a>(token: &'a str, value: &'a str) -> Result<SysctlParameter<'a>, String> {
+fn parse_path(token: &str) -> Result<Vec<&str>, ParseError> {
     let token = token.trim();
-    let value = value.trim();
     // invalid token begins or ends with a `.` or has continuous `.`
     if token.starts_with(".") || token.ends_with(".") || token.contains("..") {
-        Err(format!(
-            "Token '{}' has an invalid hierarchical structure",
-            token
+        Err(ParseError::new(
+            ParseErrorKind::InvalidToken,
+            format!("Token '{}' has an invalid hierarchical structure", token),
         ))
     // valid token
     } else {
-        let path = token.split(".").collect::<Vec<&str>>();
-        Ok(SysctlParameter { path, value })
+        Ok(token.split(".").collect::<Vec<&str>>())
     }
 }
 
-fn parse_line<'a>(line: &'a str) -> Result<Option<SysctlParameter<'a>>, String> {
+// unquote a value beginning with a quote, returning the unquoted value and the rest after it
+fn parse_quoted(value: &str) -> Result<(Cow<'_, str>, &str), ParseError> {
+    let quote = value.chars().next().unwrap();
+    let body = &value[1..];
+    let mut unquoted = String::new();
+    let mut escaped = false;
+    let mut chars = body.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            let rest = &body[index + 1..];
+            // borrow the value as is if it has no escapes
+            if !escaped {
+                return Ok((Cow::Borrowed(&body[..index]), rest));
+            }
+            return Ok((Cow::Owned(unquoted), rest));
+        } else if c == '\\' {
+            escaped = true;
+            match chars.next() {
+                Some((_, 'n')) => unquoted.push('\n'),
+                Some((_, 't')) => unquoted.push('\t'),
+                Some((_, c @ ('"' | '\'' | '\\'))) => unquoted.push(c),
+                Some((_, c)) => {
+                    return Err(ParseError::new(
+                        ParseErrorKind::InvalidValue,
+                        format!("Value '{}' has an invalid escape sequence '\\{}'", value, c),
+                    ))
+                }
+                None => break,
+            }
+        } else {
+            unquoted.push(c);
+        }
+    }
+    Err(ParseError::new(
+        ParseErrorKind::InvalidValue,
+        format!("Value '{}' has no closing quote", value),
+    ))
+}
+
+// cut a value before ` #` or ` ;` after it, so that a value such as `#ffffff` is kept
+pub(crate) fn strip_inline_comment(value: &str) -> &str {
+    let mut previous = ' ';
+    let mut seen = false;
+    for (index, c) in value.char_indices() {
+        if seen && (c == '#' || c == ';') && previous.is_whitespace() {
+            return &value[..index];
+        }
+        seen |= !c.is_whitespace();
+        previous = c;
+    }
+    value
+}
+
+// a comment after the closing quote, which needs whitespace before it as strip_inline_comment
+fn is_comment(rest: &str) -> bool {
+    let comment = rest.trim_start();
+    comment.len() < rest.len() && (comment.starts_with("#") || comment.starts_with(";"))
+}
+
+pub(crate) fn parse_value<'a>(
+    value: &'a str,
+    options: &ParseOptions,
+) -> Result<Cow<'a, str>, ParseError> {
+    let trimmed = value.trim();
+    if options.quoting && (trimmed.starts_with("\"") || trimmed.starts_with("'")) {
+        let value = trimmed;
+        let (unquoted, rest) = parse_quoted(value)?;
+        let trailing_comment = options.inline_comments && is_comment(rest);
+        if !rest.trim().is_empty() && !trailing_comment {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidValue,
+                format!(
+                    "Value '{}' has trailing characters after the closing quote",
+                    value
+                ),
+            ));
+        }
+        Ok(unquoted)
+    } else if options.inline_comments {
+        Ok(Cow::Borrowed(strip_inline_comment(value).trim()))
+    } else {
+        Ok(Cow::Borrowed(trimmed))
+    }
+}
+
+fn parse_token<'a>(
+    token: &'a str,
+    value: &'a str,
+    options: &ParseOptions,
+) -> Result<SysctlParameter<'a>, ParseError> {
+    let path = parse_path(token)?;
+    let value = SysctlParameterValue::V(parse_value(value, options)?);
+    Ok(SysctlParameter { path, value })
+}
+
+fn parse_line<'a>(
+    line: &'a str,
+    options: &ParseOptions,
+) -> Result<Option<SysctlParameter<'a>>, ParseError> {
     let line = line.trim();
     // multiple lines
     if line.contains("\n") || line.contains("\r") {
-        Err(format!("'{}' is not a single line", line))
+        Err(ParseError::new(
+            ParseErrorKind::InvalidSyntax,
+            format!("'{}' is not a single line", line),
+        ))
     }
     // empty, comment, or invalid token line
-    else if line.len() == 0
+    else if line.is_empty()
         || line.starts_with("#")
         || line.starts_with(";")
         || line.starts_with("-")
     {
         Ok(None)
+    // tombstone line deleting an inherited token
+    } else if line.starts_with("!") && !line.contains("=") {
+        let path = parse_path(&line[1..])?;
+        Ok(Some(SysctlParameter {
+            path,
+            value: SysctlParameterValue::D,
+        }))
     // invalid syntax line
     } else if !line.contains("=") {
-        Err(format!("'{}' is not in format `token = value`", line))
+        Err(ParseError::new(
+            ParseErrorKind::InvalidSyntax,
+            format!("'{}' is not in format `token = value`", line),
+        ))
     // valid syntax line
     } else {
-        let mut parameter = line.splitn(2, "=");
-        let token = parameter.next().unwrap();
-        let value = parameter.next().unwrap();
-        match parse_token(token, value) {
+        let (token, value) = line.split_once("=").unwrap();
+        match parse_token(token, value, options) {
             Ok(parameter) => Ok(Some(parameter)),
             Err(err) => Err(err),
         }
     }
 }
 
+struct LogicalLine<'a> {
+    // index of the first physical line
+    index: usize,
+    line: &'a str,
+    // all physical lines joined without `\`, if continued
+    continued: Option<String>,
+}
+
+fn is_continued(line: &str, options: &ParseOptions) -> bool {
+    let line = line.trim();
+    // comment lines are never continued
+    options.line_continuation
+        && line.ends_with("\\")
+        && !line.starts_with("#")
+        && !line.starts_with(";")
+}
+
+fn logical_lines<'a>(content: &'a str, options: &ParseOptions) -> Vec<LogicalLine<'a>> {
+    let mut logical_lines = vec![];
+    let mut lines = content.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        if !is_continued(line, options) {
+            logical_lines.push(LogicalLine {
+                index,
+                line,
+                continued: None,
+            });
+            continue;
+        }
+        let trimmed = line.trim();
+        let mut continued = trimmed[..trimmed.len() - 1].to_string();
+        for (_, next) in lines.by_ref() {
+            match next.trim_end().strip_suffix("\\") {
+                Some(next) => continued.push_str(next),
+                None => {
+                    continued.push_str(next);
+                    break;
+                }
+            }
+        }
+        logical_lines.push(LogicalLine {
+            index,
+            line,
+            continued: Some(continued),
+        });
+    }
+    logical_lines
+}
+
+// logical lines of content with the index of their first physical line, for include directives
+fn include_lines(content: &str, options: &ParseOptions) -> Vec<(usize, String)> {
+    logical_lines(content, options)
+        .into_iter()
+        .map(|logical_line| {
+            let line = match logical_line.continued {
+                Some(continued) => continued,
+                None => logical_line.line.to_string(),
+            };
+            (logical_line.index, line)
+        })
+        .collect()
+}
+
+fn parse_logical_line<'a>(
+    logical_line: &LogicalLine<'a>,
+    options: &ParseOptions,
+) -> Result<Option<SysctlParameter<'a>>, ParseError> {
+    let continued = match &logical_line.continued {
+        Some(continued) => continued,
+        None => return parse_line(logical_line.line, options),
+    };
+    let parameter = match parse_line(continued, options)? {
+        Some(parameter) => parameter,
+        None => return Ok(None),
+    };
+    // token must be on the first physical line to borrow it
+    let first = logical_line.line.trim();
+    let first = first[..first.len() - 1].trim_end();
+    let token = match (&parameter.value, first.split_once("=")) {
+        (SysctlParameterValue::D, None) => Some(&first[1..]),
+        (SysctlParameterValue::V(_), Some((token, _))) => Some(token),
+        _ => None,
+    };
+    let path = match token.map(parse_path) {
+        Some(Ok(path)) if path == parameter.path => path,
+        _ => {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidSyntax,
+                format!(
+                    "'{}' is not in format `token = value` on its first line",
+                    continued.trim()
+                ),
+            ))
+        }
+    };
+    let value = match parameter.value {
+        SysctlParameterValue::V(value) => SysctlParameterValue::V(Cow::Owned(value.into_owned())),
+        _ => SysctlParameterValue::D,
+    };
+    Ok(Some(SysctlParameter { path, value }))
+}
+
+// byte ranges of the token and the value within a line of an entry or a tombstone
+pub(crate) fn spans(line: &str, options: &ParseOptions) -> (Range<usize>, Range<usize>) {
+    let (token, value_start) = match line.split_once("=") {
+        Some((token, value)) => (token.trim(), line.len() - value.len()),
+        None => (line.trim().trim_start_matches("!").trim(), line.len()),
+    };
+    let token_start = line.find(token).unwrap_or(0);
+    let mut value = &line[value_start..];
+    let trimmed = value.trim_start();
+    if options.quoting && (trimmed.starts_with("\"") || trimmed.starts_with("'")) {
+        // value ends at the closing quote
+        if let Ok((_, rest)) = parse_quoted(trimmed) {
+            value = &value[..value.len() - rest.len()];
+        }
+    } else if options.inline_comments {
+        value = strip_inline_comment(value);
+    }
+    let value_start = value_start + value.len() - value.trim_start().len();
+    let mut value = value.trim();
+    // value of a continued line ends before `\`
+    if is_continued(line, options) {
+        value = value[..value.len() - 1].trim_end();
+    }
+    (
+        token_start..token_start + token.len(),
+        value_start..value_start + value.len(),
+    )
+}
+
+// origin of an entry if tracked
+fn origin(
+    file: Option<&str>,
+    logical_line: &LogicalLine,
+    options: &ParseOptions,
+) -> Option<Origin> {
+    if !options.origins {
+        return None;
+    }
+    let (key, value) = spans(logical_line.line, options);
+    Some(Origin {
+        file: file.map(|file| file.to_string()),
+        line: logical_line.index + 1,
+        key,
+        value,
+    })
+}
+
 pub fn parse_str<'a>(sysctl_conf: &'a str) -> Result<SysctlParameterHashMap<'a>, String> {
+    parse_str_with(sysctl_conf, &ParseOptions::default())
+}
+
+pub fn parse_str_with<'a>(
+    sysctl_conf: &'a str,
+    options: &ParseOptions,
+) -> Result<SysctlParameterHashMap<'a>, String> {
+    parse_str_diagnostic(sysctl_conf, options).map_err(|diagnostic| diagnostic.message.clone())
+}
+
+// same as parse_str_with, with the error located at its line
+pub fn parse_str_diagnostic<'a>(
+    sysctl_conf: &'a str,
+    options: &ParseOptions,
+) -> Result<SysctlParameterHashMap<'a>, Box<Diagnostic>> {
     let mut parameter = SysctlParameterHashMap::new();
-    for line in sysctl_conf.lines() {
-        let parsed = parse_line(line)?;
+    for logical_line in logical_lines(sysctl_conf, options) {
+        let parsed = parse_logical_line(&logical_line, options).map_err(|err| {
+            Box::new(Diagnostic::parse_error(
+                sysctl_conf,
+                logical_line.index,
+                "=",
+                err,
+            ))
+        })?;
         if let Some(parsed) = parsed {
-            parameter.insert(&parsed.path, parsed.value);
+            let origin = origin(None, &logical_line, options);
+            parameter.insert_with_origin(&parsed.path, parsed.value, origin);
         }
     }
     Ok(parameter)
 }
 
+pub fn load(path: &Path, options: &ParseOptions) -> Result<Sources, String> {
+    Sources::load(
+        path,
+        options.include.then_some(options.max_include_depth),
+        &|content| include_lines(content, options),
+    )
+}
+
+// load content already read such as from stdin, with includes relative to path
+pub fn load_content(
+    path: &Path,
+    content: String,
+    options: &ParseOptions,
+) -> Result<Sources, String> {
+    Sources::load_content(
+        path,
+        content,
+        options.include.then_some(options.max_include_depth),
+        &|content| include_lines(content, options),
+    )
+}
+
+fn parse_source<'a>(
+    parameter: &mut SysctlParameterHashMap<'a>,
+    sources: &'a Sources,
+    file: usize,
+    chain: &mut IncludeChain,
+    options: &ParseOptions,
+) -> Result<(), Box<Diagnostic>> {
+    let source = &sources.files[file];
+    let path = source.path.display().to_string();
+    for logical_line in logical_lines(&source.content, options) {
+        let index = logical_line.index;
+        // include directive
+        if let Some(included) = source.includes.get(&index) {
+            chain.push((file, index));
+            for &included in included {
+                parse_source(parameter, sources, included, chain, options)?;
+            }
+            chain.pop();
+            continue;
+        }
+        let parsed = parse_logical_line(&logical_line, options).map_err(|err| {
+            let mut diagnostic =
+                Diagnostic::parse_error(&source.content, index, "=", err).in_file(&path);
+            for &(file, line) in chain.iter().rev() {
+                diagnostic
+                    .notes
+                    .push(format!("included from {}", sources.location(file, line)));
+            }
+            Box::new(diagnostic)
+        })?;
+        if let Some(parsed) = parsed {
+            let origin = origin(Some(&path), &logical_line, options);
+            parameter.insert_with_origin(&parsed.path, parsed.value, origin);
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_sources<'a>(
+    sources: &'a Sources,
+    options: &ParseOptions,
+) -> Result<SysctlParameterHashMap<'a>, String> {
+    parse_sources_diagnostic(sources, options).map_err(|diagnostic| {
+        let mut err = format!(
+            "{} at {}:{}",
+            diagnostic.message,
+            diagnostic.file.unwrap_or_default(),
+            diagnostic.line
+        );
+        for note in diagnostic.notes {
+            err.push_str(&format!(", {}", note));
+        }
+        err
+    })
+}
+
+// same as parse_sources, with the error located at its file and line
+pub fn parse_sources_diagnostic<'a>(
+    sources: &'a Sources,
+    options: &ParseOptions,
+) -> Result<SysctlParameterHashMap<'a>, Box<Diagnostic>> {
+    let mut parameter = SysctlParameterHashMap::new();
+    parse_source(&mut parameter, sources, 0, &mut vec![], options)?;
+    Ok(parameter)
+}
+
+// parsed entry owning its path and value, either a literal value or a tombstone
+#[derive(Debug, PartialEq)]
+pub struct SysctlEvent {
+    pub path: Vec<String>,
+    pub value: SysctlParameterValue<'static>,
+    // 1-based number of the first physical line
+    pub line: usize,
+}
+
+// incremental parser over BufRead yielding an event per entry as it reads
+pub struct Reader<R: BufRead> {
+    reader: R,
+    options: ParseOptions,
+    line: usize,
+    done: bool,
+    // physical lines read so far, kept for sources with diagnostics
+    content: String,
+    // error which stopped reading
+    error: Option<String>,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R, options: ParseOptions) -> Self {
+        Reader {
+            reader,
+            options,
+            line: 0,
+            done: false,
+            content: String::new(),
+            error: None,
+        }
+    }
+    // all physical lines read, or the error which stopped reading
+    pub fn into_content(self) -> Result<String, String> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.content),
+        }
+    }
+    // read physical lines of the next logical line, or None at the end
+    fn read_logical_line(&mut self) -> Result<Option<String>, String> {
+        let mut content = String::new();
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Ok((!content.is_empty()).then_some(content)),
+                Ok(_) => {}
+                Err(err) => {
+                    let err = format!("cannot read line {}: {}", self.line + 1, err);
+                    self.error = Some(err.clone());
+                    return Err(err);
+                }
+            }
+            self.line += 1;
+            self.content.push_str(&line);
+            content.push_str(&line);
+            if !is_continued(&line, &self.options) {
+                return Ok(Some(content));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<SysctlEvent, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let first_line = self.line + 1;
+            let content = match self.read_logical_line() {
+                Ok(Some(content)) => content,
+                Ok(None) => break,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            let parsed = logical_lines(&content, &self.options)
+                .first()
+                .map(|logical_line| parse_logical_line(logical_line, &self.options))
+                .unwrap_or(Ok(None));
+            match parsed {
+                Ok(Some(parsed)) => {
+                    let value = match parsed.value {
+                        SysctlParameterValue::V(value) => {
+                            SysctlParameterValue::V(Cow::Owned(value.into_owned()))
+                        }
+                        _ => SysctlParameterValue::D,
+                    };
+                    return Some(Ok(SysctlEvent {
+                        path: parsed.path.iter().map(|token| token.to_string()).collect(),
+                        value,
+                        line: first_line,
+                    }));
+                }
+                Ok(None) => {}
+                Err(err) => return Some(Err(format!("{} at line {}", err.message, first_line))),
+            }
+        }
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::hashmap::SysctlParameterValue;
+    use crate::source::write_files;
     use std::collections::HashMap;
 
     #[test]
@@ -78,16 +581,20 @@ log.limit = 1024",
             result.unwrap(),
             SysctlParameterHashMap {
                 items: HashMap::from([
-                    ("endpoint", SysctlParameterValue::V("localhost:3000")),
-                    ("debug", SysctlParameterValue::V("true")),
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    ("debug", SysctlParameterValue::V("true".into())),
                     (
                         "log",
                         SysctlParameterValue::from_map(HashMap::from([
-                            ("file", SysctlParameterValue::V("/var/log/console.log")),
-                            ("limit", SysctlParameterValue::V("1024"))
+                            (
+                                "file",
+                                SysctlParameterValue::V("/var/log/console.log".into())
+                            ),
+                            ("limit", SysctlParameterValue::V("1024".into()))
                         ]))
                     )
-                ])
+                ]),
+                ..Default::default()
             }
         );
     }
@@ -105,16 +612,20 @@ log.name = default.log",
             result.unwrap(),
             SysctlParameterHashMap {
                 items: HashMap::from([
-                    ("endpoint", SysctlParameterValue::V("localhost:3000")),
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
                     (
                         "log",
                         SysctlParameterValue::from_map(HashMap::from([
-                            ("file", SysctlParameterValue::V("/var/log/console.log")),
-                            ("limit", SysctlParameterValue::V("1024")),
-                            ("name", SysctlParameterValue::V("default.log"))
+                            (
+                                "file",
+                                SysctlParameterValue::V("/var/log/console.log".into())
+                            ),
+                            ("limit", SysctlParameterValue::V("1024".into())),
+                            ("name", SysctlParameterValue::V("default.log".into()))
                         ]))
                     )
-                ])
+                ]),
+                ..Default::default()
             }
         );
     }
@@ -128,7 +639,11 @@ endpoint = localhost:3001",
         assert_eq!(
             result.unwrap(),
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3001")),])
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3001".into())
+                ),]),
+                ..Default::default()
             }
         );
     }
@@ -143,7 +658,11 @@ endpoint = localhost:3001",
         assert_eq!(
             result.unwrap(),
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3000")),])
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000".into())
+                ),]),
+                ..Default::default()
             }
         );
     }
@@ -161,7 +680,11 @@ endpoint = localhost:3000
         assert_eq!(
             result.unwrap(),
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3000")),])
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000".into())
+                ),]),
+                ..Default::default()
             }
         );
     }
@@ -175,11 +698,51 @@ endpoint = localhost:3000
         assert_eq!(
             result.unwrap(),
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3000")),])
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000".into())
+                ),]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn tombstone() {
+        let result = parse_str(
+            "endpoint = localhost:3000
+log.file = /var/log/console.log
+!log.file
+!debug",
+        );
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "file",
+                            SysctlParameterValue::D
+                        )]))
+                    ),
+                    ("debug", SysctlParameterValue::D),
+                ]),
+                ..Default::default()
             }
         );
     }
 
+    #[test]
+    fn invalid_tombstone() {
+        let result = parse_str("!.endpoint");
+        assert_eq!(
+            result,
+            Err("Token '.endpoint' has an invalid hierarchical structure".to_string())
+        );
+    }
+
     #[test]
     fn invalid_token_begins_with_dot() {
         let result = parse_str(".endpoint = localhost:3000");
@@ -215,4 +778,566 @@ endpoint = localhost:3000
             Err("'end.point.localhost:3000' is not in format `token = value`".to_string())
         );
     }
+
+    #[test]
+    fn origins() {
+        let options = ParseOptions {
+            quoting: true,
+            inline_comments: true,
+            line_continuation: true,
+            origins: true,
+            ..Default::default()
+        };
+        let result = parse_str_with(
+            "  endpoint=localhost:3000
+log.name = \"a # b\" # name
+log.limit = 1024 # limit
+!debug
+log.file = /var/log/ \\
+  console.log",
+            &options,
+        )
+        .unwrap();
+        let spans = |token: &str| {
+            result
+                .origin(token)
+                .map(|origin| (origin.line, origin.key.clone(), origin.value.clone()))
+        };
+        assert_eq!(spans("endpoint"), Some((1, 2..10, 11..25)));
+        assert_eq!(spans("log.name"), Some((2, 0..8, 11..18)));
+        assert_eq!(spans("log.limit"), Some((3, 0..9, 12..16)));
+        assert_eq!(spans("log.file"), Some((5, 0..8, 11..20)));
+        // tombstones have no literal value
+        assert_eq!(spans("debug"), None);
+
+        // not tracked by default
+        let result = parse_str("endpoint = localhost:3000").unwrap();
+        assert_eq!(result.origin("endpoint"), None);
+        assert!(result.origins.is_empty());
+    }
+
+    #[test]
+    fn quoting() {
+        let options = ParseOptions {
+            quoting: true,
+            ..Default::default()
+        };
+        let result = parse_str_with(
+            r#"prompt = "  > "
+comment = '# not a comment'
+banner = "line1\nline2\t\"quoted\" \\ 'single'"
+single = 'it\'s'
+empty = ""
+raw = raw "value""#,
+            &options,
+        );
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("prompt", SysctlParameterValue::V("  > ".into())),
+                    ("comment", SysctlParameterValue::V("# not a comment".into())),
+                    (
+                        "banner",
+                        SysctlParameterValue::V("line1\nline2\t\"quoted\" \\ 'single'".into())
+                    ),
+                    ("single", SysctlParameterValue::V("it's".into())),
+                    ("empty", SysctlParameterValue::V("".into())),
+                    ("raw", SysctlParameterValue::V("raw \"value\"".into())),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn quoting_disabled() {
+        let result = parse_str(r#"prompt = "  > ""#);
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([("prompt", SysctlParameterValue::V("\"  > \"".into()))]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_quoting() {
+        let options = ParseOptions {
+            quoting: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_str_with(r#"prompt = "  > "#, &options),
+            Err(r#"Value '"  >' has no closing quote"#.to_string())
+        );
+        assert_eq!(
+            parse_str_with(r#"prompt = "\e""#, &options),
+            Err(r#"Value '"\e"' has an invalid escape sequence '\e'"#.to_string())
+        );
+        assert_eq!(
+            parse_str_with(r#"prompt = "  > " >"#, &options),
+            Err(r#"Value '"  > " >' has trailing characters after the closing quote"#.to_string())
+        );
+    }
+
+    #[test]
+    fn inline_comments() {
+        let options = ParseOptions {
+            inline_comments: true,
+            ..Default::default()
+        };
+        let result = parse_str_with(
+            "endpoint = localhost:3000  # staging
+debug = true;false ; debug
+color = #ffffff
+background = #000000 # black
+separator = ; ;",
+            &options,
+        );
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    ("debug", SysctlParameterValue::V("true;false".into())),
+                    ("color", SysctlParameterValue::V("#ffffff".into())),
+                    ("background", SysctlParameterValue::V("#000000".into())),
+                    ("separator", SysctlParameterValue::V(";".into())),
+                ]),
+                ..Default::default()
+            }
+        );
+
+        let result = parse_str("endpoint = localhost:3000  # staging");
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000  # staging".into())
+                )]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn inline_comments_with_quoting() {
+        let options = ParseOptions {
+            quoting: true,
+            inline_comments: true,
+            ..Default::default()
+        };
+        let result = parse_str_with(
+            r##"color = "#ffffff" # white
+prompt = '; > ' ;prompt
+raw = a"b # c"##,
+            &options,
+        );
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("color", SysctlParameterValue::V("#ffffff".into())),
+                    ("prompt", SysctlParameterValue::V("; > ".into())),
+                    ("raw", SysctlParameterValue::V("a\"b".into())),
+                ]),
+                ..Default::default()
+            }
+        );
+        // a comment needs whitespace before it
+        assert_eq!(
+            parse_str_with("prompt = '; > ';prompt", &options),
+            Err(
+                "Value ''; > ';prompt' has trailing characters after the closing quote".to_string()
+            )
+        );
+        assert_eq!(
+            parse_str_with("color = \"#ffffff\"#white", &options),
+            Err(
+                r##"Value '"#ffffff"#white' has trailing characters after the closing quote"##
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn line_continuation() {
+        let options = ParseOptions {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let result = parse_str_with(
+            "kernel.core_pattern = |/usr/lib/systemd/systemd-coredump \\
+  %P %u %g \\
+%s
+# comment \\
+endpoint = localhost:3000
+",
+            &options,
+        );
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    (
+                        "kernel",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "core_pattern",
+                            SysctlParameterValue::V(
+                                "|/usr/lib/systemd/systemd-coredump   %P %u %g %s".into()
+                            )
+                        )]))
+                    ),
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                ]),
+                ..Default::default()
+            }
+        );
+
+        // continued lines are checked as a single line
+        let result = parse_str_with(
+            "-log.file = a \\\n b\n! log.limit \\\n\nendpoint = x",
+            &options,
+        );
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "limit",
+                            SysctlParameterValue::D
+                        )]))
+                    ),
+                    ("endpoint", SysctlParameterValue::V("x".into())),
+                ]),
+                ..Default::default()
+            }
+        );
+
+        let result = parse_str("endpoint = localhost:3000 \\");
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000 \\".into())
+                )]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_line_continuation() {
+        let dir = write_files(
+            "invalid_line_continuation",
+            &[(
+                "sysctl.conf",
+                "endpoint = localhost:3000\nlog\\\n.file = /var/log/console.log\ndebug = true",
+            )],
+        );
+        let options = ParseOptions {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let sources = load(&dir.join("sysctl.conf"), &options).unwrap();
+        let result = parse_sources(&sources, &options);
+        assert_eq!(
+            result,
+            Err(format!(
+                "'log.file = /var/log/console.log' is not in format `token = value` on its first line at {}:2",
+                dir.join("sysctl.conf").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn reader() {
+        let options = ParseOptions {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let content = "endpoint = localhost:3000\r
+# debug = true
+log.file = /var/log/\\
+console.log
+!log.limit
+log..name = default.log
+log.name = default.log";
+        let mut reader = Reader::new(content.as_bytes(), options);
+        assert_eq!(
+            reader.by_ref().collect::<Vec<_>>(),
+            vec![
+                Ok(SysctlEvent {
+                    path: vec!["endpoint".to_string()],
+                    value: SysctlParameterValue::V("localhost:3000".into()),
+                    line: 1,
+                }),
+                Ok(SysctlEvent {
+                    path: vec!["log".to_string(), "file".to_string()],
+                    value: SysctlParameterValue::V("/var/log/console.log".into()),
+                    line: 3,
+                }),
+                Ok(SysctlEvent {
+                    path: vec!["log".to_string(), "limit".to_string()],
+                    value: SysctlParameterValue::D,
+                    line: 5,
+                }),
+                Err(
+                    "Token 'log..name' has an invalid hierarchical structure at line 6".to_string()
+                ),
+                Ok(SysctlEvent {
+                    path: vec!["log".to_string(), "name".to_string()],
+                    value: SysctlParameterValue::V("default.log".into()),
+                    line: 7,
+                }),
+            ]
+        );
+        assert_eq!(reader.into_content(), Ok(content.to_string()));
+    }
+
+    #[test]
+    fn include() {
+        let dir = write_files(
+            "include",
+            &[
+                (
+                    "sysctl.conf",
+                    "endpoint = localhost:3000
+include net.conf
+include conf.d/*.conf
+debug = false",
+                ),
+                ("net.conf", "endpoint = localhost:3001"),
+                ("conf.d/10-log.conf", "log.limit = 1024\ndebug = true"),
+                ("conf.d/20-log.conf", "log.limit = 2048"),
+                ("conf.d/log.conf.bak", "log.limit = 4096"),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let sources = load(&dir.join("sysctl.conf"), &options).unwrap();
+        let result = parse_sources(&sources, &options);
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("endpoint", SysctlParameterValue::V("localhost:3001".into())),
+                    ("debug", SysctlParameterValue::V("false".into())),
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "limit",
+                            SysctlParameterValue::V("2048".into())
+                        )]))
+                    ),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn include_with_line_continuation() {
+        let dir = write_files(
+            "include_with_line_continuation",
+            &[
+                (
+                    "sysctl.conf",
+                    "banner = hello \\\ninclude net.conf\ninclude \\\nlog.conf",
+                ),
+                ("net.conf", "endpoint = localhost:3001"),
+                ("log.conf", "log.limit = 1024"),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            line_continuation: true,
+            ..Default::default()
+        };
+        let sources = load(&dir.join("sysctl.conf"), &options).unwrap();
+        // the include in the continued value is not loaded
+        assert_eq!(sources.files.len(), 2);
+        let result = parse_sources(&sources, &options);
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    (
+                        "banner",
+                        SysctlParameterValue::V("hello include net.conf".into())
+                    ),
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "limit",
+                            SysctlParameterValue::V("1024".into())
+                        )]))
+                    ),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn include_from_content() {
+        let dir = write_files(
+            "include_from_content",
+            &[("net.conf", "endpoint = localhost:3001")],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let sources = load_content(
+            &dir.join("<stdin>"),
+            "endpoint = localhost:3000\ninclude net.conf".to_string(),
+            &options,
+        )
+        .unwrap();
+        let result = parse_sources(&sources, &options);
+        assert_eq!(
+            result.unwrap(),
+            SysctlParameterHashMap {
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3001".into())
+                )]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn include_disabled() {
+        let dir = write_files(
+            "include_disabled",
+            &[("sysctl.conf", "include net.conf"), ("net.conf", "")],
+        );
+        let options = ParseOptions::default();
+        let sources = load(&dir.join("sysctl.conf"), &options).unwrap();
+        let result = parse_sources(&sources, &options);
+        assert_eq!(
+            result,
+            Err(format!(
+                "'include net.conf' is not in format `token = value` at {}:1",
+                dir.join("sysctl.conf").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn include_error_chain() {
+        let dir = write_files(
+            "include_error_chain",
+            &[
+                (
+                    "sysctl.conf",
+                    "endpoint = localhost:3000\ninclude net/net.conf",
+                ),
+                ("net/net.conf", "# net\ninclude ../log.conf"),
+                ("log.conf", "log..limit = 1024"),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let sources = load(&dir.join("sysctl.conf"), &options).unwrap();
+        let result = parse_sources(&sources, &options);
+        assert_eq!(
+            result,
+            Err(format!(
+                "Token 'log..limit' has an invalid hierarchical structure at {}:1, included from {}:2, included from {}:2",
+                dir.join("net/../log.conf").display(),
+                dir.join("net/net.conf").display(),
+                dir.join("sysctl.conf").display()
+            ))
+        );
+
+        let result = parse_sources_diagnostic(&sources, &options).map(|_| ());
+        let diagnostic = *result.unwrap_err();
+        assert_eq!(
+            (
+                diagnostic.code.as_str(),
+                diagnostic.file,
+                diagnostic.line,
+                diagnostic.column,
+                diagnostic.token
+            ),
+            (
+                "invalid-token",
+                Some(dir.join("net/../log.conf").display().to_string()),
+                1,
+                1,
+                Some("log..limit".to_string())
+            )
+        );
+        assert_eq!(
+            diagnostic.notes,
+            vec![
+                format!("included from {}:2", dir.join("net/net.conf").display()),
+                format!("included from {}:2", dir.join("sysctl.conf").display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = write_files(
+            "include_cycle",
+            &[
+                ("sysctl.conf", "include net.conf"),
+                ("net.conf", "include sysctl.conf"),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            ..Default::default()
+        };
+        let result = load(&dir.join("sysctl.conf"), &options).map(|_| ());
+        assert_eq!(
+            result,
+            Err(format!(
+                "include cycle detected for '{}' at {}:1, included from {}:1",
+                dir.join("sysctl.conf").display(),
+                dir.join("net.conf").display(),
+                dir.join("sysctl.conf").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn include_depth() {
+        let dir = write_files(
+            "include_depth",
+            &[
+                ("sysctl.conf", "include a.conf"),
+                ("a.conf", "include b.conf"),
+                ("b.conf", "endpoint = localhost:3000"),
+            ],
+        );
+        let options = ParseOptions {
+            include: true,
+            max_include_depth: 1,
+            ..Default::default()
+        };
+        let result = load(&dir.join("sysctl.conf"), &options).map(|_| ());
+        assert_eq!(
+            result,
+            Err(format!(
+                "include depth exceeds 1 at {}:1, included from {}:1",
+                dir.join("a.conf").display(),
+                dir.join("sysctl.conf").display()
+            ))
+        );
+    }
 }
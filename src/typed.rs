@@ -0,0 +1,489 @@
+use crate::{
+    constraint::Constraint,
+    hashmap::{SysctlParameterHashMap, SysctlParameterValue},
+    normalize,
+    schema::{SchemaHashMap, SchemaType},
+};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Integer(u64),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+    // submap with tokens sorted
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    // value of a dotted token such as `log.limit`
+    pub fn get(&self, token: &str) -> Option<&Value> {
+        token.split(".").try_fold(self, |value, token| match value {
+            Value::Map(map) => map.get(token),
+            _ => None,
+        })
+    }
+}
+
+// split a list value by `,`, or by whitespace if no `,`
+fn split_list(value: &str) -> Vec<&str> {
+    if value.trim().is_empty() {
+        vec![]
+    } else if value.contains(",") {
+        value.split(",").map(|item| item.trim()).collect()
+    } else {
+        value.split_whitespace().collect()
+    }
+}
+
+// convert a literal value of the token into the schema type
+pub fn convert_value(token: &str, value: &str, schema_type: &SchemaType) -> Result<Value, String> {
+    match schema_type {
+        SchemaType::Bool() => value
+            .parse::<bool>()
+            .map(Value::Bool)
+            .map_err(|_| format!("'{}' has not a bool value '{}'", token, value)),
+        SchemaType::Integer() => value
+            .parse::<u64>()
+            .map(Value::Integer)
+            .map_err(|_| format!("'{}' has not a integer value '{}'", token, value)),
+        SchemaType::Float() => match value.parse::<f64>() {
+            Ok(float) if float.is_finite() => Ok(Value::Float(float)),
+            _ => Err(format!("'{}' has not a float value '{}'", token, value)),
+        },
+        SchemaType::Size() => normalize::parse_size(value)
+            .map(Value::Integer)
+            .ok_or_else(|| format!("'{}' has not a size value '{}'", token, value)),
+        SchemaType::Duration() => normalize::parse_duration(value)
+            .map(Value::Integer)
+            .ok_or_else(|| format!("'{}' has not a duration value '{}'", token, value)),
+        SchemaType::Normalized(normalized) => match normalized.as_ref() {
+            SchemaType::Bool() => normalize::parse_bool(value)
+                .map(Value::Bool)
+                .ok_or_else(|| format!("'{}' has not a bool value '{}'", token, value)),
+            SchemaType::Integer() => normalize::parse_integer(value)
+                .map(Value::Integer)
+                .ok_or_else(|| format!("'{}' has not a integer value '{}'", token, value)),
+            normalized => convert_value(token, value, normalized),
+        },
+        SchemaType::String() => Ok(Value::String(value.to_string())),
+        SchemaType::Optional(optional) => convert_value(token, value, optional),
+        SchemaType::Map(_) | SchemaType::Object() => {
+            Err(format!("'{}' is not a submap, is a literal value", token))
+        }
+        SchemaType::List(item) => split_list(value)
+            .into_iter()
+            .map(|value| convert_value(token, value, item))
+            .collect::<Result<Vec<Value>, String>>()
+            .map(Value::List),
+    }
+}
+
+fn is_normalizing(schema_type: &SchemaType) -> bool {
+    matches!(
+        schema_type,
+        SchemaType::Size() | SchemaType::Duration() | SchemaType::Normalized(_)
+    )
+}
+
+// canonical form of a value of a normalizing type, or None to keep the value as it is
+pub fn canonical_value(
+    token: &str,
+    value: &str,
+    schema_type: &SchemaType,
+) -> Result<Option<String>, String> {
+    let schema_type = schema_type.base();
+    let converted = convert_value(token, value, schema_type)?;
+    match (schema_type, converted) {
+        (SchemaType::List(item), Value::List(items)) if is_normalizing(item) => {
+            let separator = if value.contains(",") { ", " } else { " " };
+            let items = items
+                .iter()
+                .map(|item| match item {
+                    Value::Bool(value) => value.to_string(),
+                    Value::Integer(value) => value.to_string(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<String>>();
+            Ok(Some(items.join(separator)))
+        }
+        (schema_type, Value::Bool(value)) if is_normalizing(schema_type) => {
+            Ok(Some(value.to_string()))
+        }
+        (schema_type, Value::Integer(value)) if is_normalizing(schema_type) => {
+            Ok(Some(value.to_string()))
+        }
+        _ => Ok(None),
+    }
+}
+
+type Converted = (String, Result<Value, (&'static str, String)>);
+
+// convert all tokens of the schema walking it along with the config, returning an error code and
+// message on failure of each token, sorted by token
+pub(crate) fn convert_tokens(
+    sysctl_conf: &SysctlParameterHashMap,
+    schema: &SchemaHashMap,
+) -> Vec<Converted> {
+    let mut converted = vec![];
+    for (token, child) in &schema.items {
+        convert_node(
+            sysctl_conf.items.get(token),
+            child,
+            token.to_string(),
+            &mut converted,
+        );
+    }
+    converted.sort_by(|a, b| a.0.cmp(&b.0));
+    converted
+}
+
+fn convert_node(
+    value: Option<&SysctlParameterValue>,
+    schema: &SchemaHashMap,
+    token: String,
+    converted: &mut Vec<Converted>,
+) {
+    let schema_type = match (&schema.schema_type, value) {
+        // optional token which is not set
+        (Some(SchemaType::Optional(_)), None | Some(SysctlParameterValue::D)) => None,
+        (schema_type, _) => schema_type.as_ref().map(SchemaType::base),
+    };
+    match (schema_type, value) {
+        (Some(SchemaType::Map(item)), Some(SysctlParameterValue::M(map))) => {
+            converted.push((token.clone(), Ok(Value::Map(BTreeMap::new()))));
+            for (path, value) in map.leaves() {
+                // leaves with their own types or under a nested map are converted by them
+                let covered = match path.split_first() {
+                    Some((first, rest)) => schema
+                        .items
+                        .get(first)
+                        .is_some_and(|child| child.resolve_path(rest).is_some()),
+                    None => false,
+                };
+                if covered {
+                    continue;
+                }
+                let token = format!("{}.{}", token, path.join("."));
+                let value =
+                    convert_value(&token, value, item).map_err(|err| ("type-mismatch", err));
+                converted.push((token, value));
+            }
+        }
+        (Some(SchemaType::Object()), Some(SysctlParameterValue::M(_))) => {
+            converted.push((token.clone(), Ok(Value::Map(BTreeMap::new()))));
+        }
+        (Some(SchemaType::Map(_) | SchemaType::Object()), Some(SysctlParameterValue::V(_))) => {
+            converted.push((
+                token.clone(),
+                Err((
+                    "unexpected-literal",
+                    format!("'{}' is not a submap, is a literal value", token),
+                )),
+            ));
+        }
+        (Some(schema_type), value) => {
+            converted.push((token.clone(), convert_literal(value, &token, schema_type)));
+        }
+        (None, _) => {}
+    }
+    for (child_token, child) in &schema.items {
+        let child_value = match value {
+            Some(SysctlParameterValue::M(map)) => map.items.get(child_token),
+            _ => None,
+        };
+        convert_node(
+            child_value,
+            child,
+            format!("{}.{}", token, child_token),
+            converted,
+        );
+    }
+}
+
+fn convert_literal(
+    value: Option<&SysctlParameterValue>,
+    token: &str,
+    schema_type: &SchemaType,
+) -> Result<Value, (&'static str, String)> {
+    match value {
+        Some(SysctlParameterValue::V(value)) => {
+            convert_value(token, value, schema_type).map_err(|err| ("type-mismatch", err))
+        }
+        Some(SysctlParameterValue::M(_)) => Err((
+            "unexpected-submap",
+            format!("'{}' is not a literal value, is a submap", token),
+        )),
+        _ => Err(("missing-token", format!("'{}' is not found", token))),
+    }
+}
+
+// token to locate each violated constraint at and its message, evaluated with the values which
+// passed the type check
+pub(crate) fn check_constraints(
+    sysctl_conf: &SysctlParameterHashMap,
+    schema: &SchemaHashMap,
+    converted: &[Converted],
+) -> Vec<(String, String)> {
+    let values = constraint_values(converted);
+    schema
+        .constraints
+        .iter()
+        .filter_map(|constraint| constraint.check(sysctl_conf, &values))
+        .collect()
+}
+
+// constraints violated by the config, in order
+pub(crate) fn violated_constraints<'s>(
+    sysctl_conf: &SysctlParameterHashMap,
+    schema: &'s SchemaHashMap,
+) -> Vec<&'s Constraint> {
+    let values = constraint_values(&convert_tokens(sysctl_conf, schema));
+    schema
+        .constraints
+        .iter()
+        .filter(|constraint| constraint.check(sysctl_conf, &values).is_some())
+        .collect()
+}
+
+fn constraint_values(converted: &[Converted]) -> HashMap<String, Value> {
+    converted
+        .iter()
+        .filter_map(|(token, value)| Some((token.clone(), value.as_ref().ok()?.clone())))
+        .collect()
+}
+
+fn insert(map: &mut BTreeMap<String, Value>, path: &[&str], value: Value) {
+    match path {
+        [] => {}
+        [token] => {
+            map.insert(token.to_string(), value);
+        }
+        [token, path @ ..] => {
+            let child = map
+                .entry(token.to_string())
+                .or_insert_with(|| Value::Map(BTreeMap::new()));
+            if let Value::Map(child) = child {
+                insert(child, path, value);
+            }
+        }
+    }
+}
+
+// validate and convert all literal values into a Map, where values of tokens not in the schema are strings
+pub fn convert(
+    sysctl_conf: &SysctlParameterHashMap,
+    schema: &SchemaHashMap,
+) -> Result<Value, String> {
+    let tokens = convert_tokens(sysctl_conf, schema);
+    let mut converted = BTreeMap::new();
+    for (token, value) in &tokens {
+        let value = value.clone().map_err(|(_, err)| err)?;
+        insert(
+            &mut converted,
+            &token.split(".").collect::<Vec<&str>>(),
+            value,
+        );
+    }
+    if let Some((_, err)) = check_constraints(sysctl_conf, schema, &tokens)
+        .into_iter()
+        .next()
+    {
+        return Err(err);
+    }
+    for (path, value) in sysctl_conf.leaves() {
+        if schema.resolve_path(&path).is_none() {
+            insert(&mut converted, &path, Value::String(value.to_string()));
+        }
+    }
+    Ok(Value::Map(converted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{schema, sysctl::parse_str};
+
+    #[test]
+    fn typed() {
+        let sysctl_conf = parse_str(
+            "endpoint = localhost:3000
+debug = true
+log.limit = 1024
+log.ratio = 0.5
+net.ports = 8080, 8081
+net.hosts = a.local b.local
+net.empty =",
+        )
+        .unwrap();
+        let schema = schema::parse_str(
+            "debug => bool
+log.limit => integer
+log.ratio => float
+net.ports => list(integer)
+net.hosts => list(string)
+net.empty => list(bool)",
+        )
+        .unwrap();
+        let result = convert(&sysctl_conf, &schema).unwrap();
+        assert_eq!(
+            result,
+            Value::Map(BTreeMap::from([
+                (
+                    "endpoint".to_string(),
+                    Value::String("localhost:3000".to_string())
+                ),
+                ("debug".to_string(), Value::Bool(true)),
+                (
+                    "log".to_string(),
+                    Value::Map(BTreeMap::from([
+                        ("limit".to_string(), Value::Integer(1024)),
+                        ("ratio".to_string(), Value::Float(0.5)),
+                    ]))
+                ),
+                (
+                    "net".to_string(),
+                    Value::Map(BTreeMap::from([
+                        (
+                            "ports".to_string(),
+                            Value::List(vec![Value::Integer(8080), Value::Integer(8081)])
+                        ),
+                        (
+                            "hosts".to_string(),
+                            Value::List(vec![
+                                Value::String("a.local".to_string()),
+                                Value::String("b.local".to_string())
+                            ])
+                        ),
+                        ("empty".to_string(), Value::List(vec![])),
+                    ]))
+                ),
+            ]))
+        );
+        assert_eq!(result.get("log.limit"), Some(&Value::Integer(1024)));
+        assert_eq!(result.get("log.limit.none"), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let sysctl_conf = parse_str("net.ports = 8080, http\nlog.ratio = inf").unwrap();
+        let result = convert(
+            &sysctl_conf,
+            &schema::parse_str("net.ports => list(integer)").unwrap(),
+        );
+        assert_eq!(
+            result,
+            Err("'net.ports' has not a integer value 'http'".to_string())
+        );
+        let result = convert(
+            &sysctl_conf,
+            &schema::parse_str("log.ratio => float").unwrap(),
+        );
+        assert_eq!(
+            result,
+            Err("'log.ratio' has not a float value 'inf'".to_string())
+        );
+        let result = convert(&sysctl_conf, &schema::parse_str("log => string").unwrap());
+        assert_eq!(
+            result,
+            Err("'log' is not a literal value, is a submap".to_string())
+        );
+    }
+
+    #[test]
+    fn normalized() {
+        let sysctl_conf =
+            parse_str("debug = Yes\nlog.mode = 0755\nlog.limit = 64K\nlog.rotate = 5m").unwrap();
+        let result = convert(
+            &sysctl_conf,
+            &schema::parse_str(
+                "debug => normalized(bool)
+log.mode => normalized(integer)
+log.limit => size
+log.rotate => duration",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(result.get("debug"), Some(&Value::Bool(true)));
+        assert_eq!(result.get("log.mode"), Some(&Value::Integer(493)));
+        assert_eq!(result.get("log.limit"), Some(&Value::Integer(65536)));
+        assert_eq!(result.get("log.rotate"), Some(&Value::Integer(300)));
+
+        let normalized_integer = SchemaType::Normalized(Box::new(SchemaType::Integer()));
+        assert_eq!(
+            canonical_value("log.mode", "0x1f", &normalized_integer),
+            Ok(Some("31".to_string()))
+        );
+        assert_eq!(
+            canonical_value(
+                "log.levels",
+                "0x1f 010",
+                &SchemaType::List(Box::new(normalized_integer))
+            ),
+            Ok(Some("31 8".to_string()))
+        );
+        assert_eq!(
+            canonical_value("log.mode", "0755", &SchemaType::Integer()),
+            Ok(None)
+        );
+        assert_eq!(
+            canonical_value("log.limit", "64X", &SchemaType::Size()),
+            Err("'log.limit' has not a size value '64X'".to_string())
+        );
+    }
+
+    #[test]
+    fn submaps() {
+        let sysctl_conf = parse_str(
+            "net.conf.eth0.mtu = 1500
+net.conf.eth0.queue.len = 64
+net.conf.lo.mtu = 65536
+net.conf.lo.name = loopback
+app.tenant.a = x",
+        )
+        .unwrap();
+        let schema = schema::parse_str(
+            "net.conf => map(integer)
+net.conf.lo.name => string
+app => object",
+        )
+        .unwrap();
+        let result = convert(&sysctl_conf, &schema).unwrap();
+        assert_eq!(result.get("net.conf.eth0.mtu"), Some(&Value::Integer(1500)));
+        assert_eq!(
+            result.get("net.conf.eth0.queue.len"),
+            Some(&Value::Integer(64))
+        );
+        assert_eq!(
+            result.get("net.conf.lo.name"),
+            Some(&Value::String("loopback".to_string()))
+        );
+        assert_eq!(
+            result.get("app.tenant.a"),
+            Some(&Value::String("x".to_string()))
+        );
+
+        let empty = parse_str("net.conf.eth0.mtu = 1500\napp = x").unwrap();
+        let result = convert(
+            &empty,
+            &schema::parse_str("net.conf.lo => map(integer)").unwrap(),
+        );
+        assert_eq!(result, Err("'net.conf.lo' is not found".to_string()));
+        let result = convert(&empty, &schema);
+        assert_eq!(
+            result,
+            Err("'app' is not a submap, is a literal value".to_string())
+        );
+        let result = convert(
+            &sysctl_conf,
+            &schema::parse_str("net.conf => map(bool)").unwrap(),
+        );
+        assert_eq!(
+            result,
+            Err("'net.conf.eth0.mtu' has not a bool value '1500'".to_string())
+        );
+    }
+}
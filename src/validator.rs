@@ -1,45 +1,102 @@
 use crate::{
-    hashmap::{SysctlParameterHashMap, SysctlParameterValue},
-    schema::{SchemaHashMap, SchemaType},
+    diagnostic::{Diagnostic, Severity},
+    hashmap::SysctlParameterHashMap,
+    schema::SchemaHashMap,
+    typed::{check_constraints, convert_tokens},
 };
 
-fn validate_value(path: &str, value: &str, schema_type: SchemaType) -> Result<(), String> {
-    match schema_type {
-        SchemaType::Bool() => {
-            if let Ok(_) = value.parse::<bool>() {
-                Ok(())
-            } else {
-                Err(format!("'{}' has not a bool value '{}'", path, value))
-            }
-        }
-        SchemaType::Integer() => {
-            if let Ok(_) = value.parse::<u64>() {
-                Ok(())
-            } else {
-                Err(format!("'{}' has not a integer value '{}'", path, value))
-            }
-        }
-        SchemaType::String() => Ok(()),
+pub fn validate(sysctl_conf: SysctlParameterHashMap, schema: SchemaHashMap) -> Result<(), String> {
+    let converted = convert_tokens(&sysctl_conf, &schema);
+    for (_, value) in &converted {
+        value.clone().map_err(|(_, err)| err)?;
+    }
+    match check_constraints(&sysctl_conf, &schema, &converted)
+        .into_iter()
+        .next()
+    {
+        Some((_, err)) => Err(err),
+        None => Ok(()),
     }
 }
 
-pub fn validate(sysctl_conf: SysctlParameterHashMap, schema: SchemaHashMap) -> Result<(), String> {
-    for s in schema {
-        let path = s.0.split(".").collect::<Vec<&str>>();
-        match sysctl_conf.get(&path) {
-            Some(SysctlParameterValue::V(value)) => validate_value(s.0, value, s.1),
-            Some(SysctlParameterValue::M(_)) => {
-                Err(format!("'{}' is not a literal value, is a submap", s.0))
+// error located at the value of the token
+fn located(
+    sysctl_conf: &SysctlParameterHashMap,
+    token: String,
+    diagnostic: Diagnostic,
+) -> Diagnostic {
+    let mut diagnostic = Diagnostic {
+        token: Some(token.clone()),
+        ..diagnostic
+    };
+    if let Some(origin) = sysctl_conf.origin(&token) {
+        diagnostic.file = origin.file.clone();
+        diagnostic.line = origin.line;
+        diagnostic.column = origin.value.start + 1;
+        diagnostic.length = origin.value.len();
+    }
+    diagnostic
+}
+
+// all errors sorted by token, followed by violated constraints in order, instead of the first one,
+// and warnings of deprecated tokens sorted by token
+pub fn validate_diagnostics(
+    sysctl_conf: SysctlParameterHashMap,
+    schema: SchemaHashMap,
+) -> Vec<Diagnostic> {
+    let converted = convert_tokens(&sysctl_conf, &schema);
+    let mut diagnostics = converted
+        .iter()
+        .filter_map(|(token, value)| {
+            let (code, message) = value.as_ref().err()?;
+            Some(located(
+                &sysctl_conf,
+                token.clone(),
+                Diagnostic::error(code, message.clone()),
+            ))
+        })
+        .collect::<Vec<Diagnostic>>();
+    for (token, message) in check_constraints(&sysctl_conf, &schema, &converted) {
+        diagnostics.push(located(
+            &sysctl_conf,
+            token,
+            Diagnostic::error("constraint-violation", message),
+        ));
+    }
+    for (token, node) in schema.nodes() {
+        let path = token.split(".").collect::<Vec<&str>>();
+        if !node.annotations.deprecated || sysctl_conf.get(&path).is_none() {
+            continue;
+        }
+        let message = match &node.annotations.replacement {
+            Some(replacement) => {
+                format!("'{}' is deprecated, use '{}' instead", token, replacement)
             }
-            _ => Err(format!("'{}' is not found", s.0)),
-        }?
+            None => format!("'{}' is deprecated", token),
+        };
+        let mut diagnostic = located(
+            &sysctl_conf,
+            token.clone(),
+            Diagnostic {
+                severity: Severity::Warning,
+                ..Diagnostic::error("deprecated-token", message)
+            },
+        );
+        // at the token rather than its value
+        if let Some(origin) = sysctl_conf.origin(&token) {
+            diagnostic.column = origin.key.start + 1;
+            diagnostic.length = origin.key.len();
+        }
+        diagnostics.push(diagnostic);
     }
-    Ok(())
+    diagnostics
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_format)]
 mod tests {
     use super::*;
+    use crate::{hashmap::SysctlParameterValue, schema::SchemaType};
     use std::collections::HashMap;
 
     #[test]
@@ -47,16 +104,20 @@ mod tests {
         let result = validate(
             SysctlParameterHashMap {
                 items: HashMap::from([
-                    ("endpoint", SysctlParameterValue::V("localhost:3000")),
-                    ("debug", SysctlParameterValue::V("true")),
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    ("debug", SysctlParameterValue::V("true".into())),
                     (
                         "log",
                         SysctlParameterValue::from_map(HashMap::from([
-                            ("file", SysctlParameterValue::V("/var/log/console.log")),
-                            ("limit", SysctlParameterValue::V("1024")),
+                            (
+                                "file",
+                                SysctlParameterValue::V("/var/log/console.log".into()),
+                            ),
+                            ("limit", SysctlParameterValue::V("1024".into())),
                         ])),
                     ),
                 ]),
+                ..Default::default()
             },
             SchemaHashMap::from([
                 ("endpoint", SchemaType::String()),
@@ -72,7 +133,11 @@ mod tests {
     fn invalid_sysctl_conf_no_such_token() {
         let result = validate(
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3000"))]),
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000".into()),
+                )]),
+                ..Default::default()
             },
             SchemaHashMap::from([("log.limit", SchemaType::Integer())]),
         );
@@ -84,16 +149,20 @@ mod tests {
         let result = validate(
             SysctlParameterHashMap {
                 items: HashMap::from([
-                    ("endpoint", SysctlParameterValue::V("localhost:3000")),
-                    ("debug", SysctlParameterValue::V("true")),
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    ("debug", SysctlParameterValue::V("true".into())),
                     (
                         "log",
                         SysctlParameterValue::from_map(HashMap::from([
-                            ("file", SysctlParameterValue::V("/var/log/console.log")),
-                            ("limit", SysctlParameterValue::V("1024")),
+                            (
+                                "file",
+                                SysctlParameterValue::V("/var/log/console.log".into()),
+                            ),
+                            ("limit", SysctlParameterValue::V("1024".into())),
                         ])),
                     ),
                 ]),
+                ..Default::default()
             },
             SchemaHashMap::from([("log", SchemaType::String())]),
         );
@@ -107,7 +176,11 @@ mod tests {
     fn invalid_sysctl_conf_invalid_bool() {
         let result = validate(
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3000"))]),
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000".into()),
+                )]),
+                ..Default::default()
             },
             SchemaHashMap::from([("endpoint", SchemaType::Bool())]),
         );
@@ -121,7 +194,11 @@ mod tests {
     fn invalid_sysctl_conf_invalid_integer() {
         let result = validate(
             SysctlParameterHashMap {
-                items: HashMap::from([("endpoint", SysctlParameterValue::V("localhost:3000"))]),
+                items: HashMap::from([(
+                    "endpoint",
+                    SysctlParameterValue::V("localhost:3000".into()),
+                )]),
+                ..Default::default()
             },
             SchemaHashMap::from([("endpoint", SchemaType::Integer())]),
         );
@@ -132,4 +209,180 @@ mod tests {
             )),
         );
     }
+
+    #[test]
+    fn diagnostics() {
+        let result = validate_diagnostics(
+            SysctlParameterHashMap {
+                items: HashMap::from([
+                    ("endpoint", SysctlParameterValue::V("localhost:3000".into())),
+                    (
+                        "log",
+                        SysctlParameterValue::from_map(HashMap::from([(
+                            "file",
+                            SysctlParameterValue::V("/var/log/console.log".into()),
+                        )])),
+                    ),
+                ]),
+                ..Default::default()
+            },
+            SchemaHashMap::from([
+                ("endpoint", SchemaType::Integer()),
+                ("log", SchemaType::String()),
+                ("log.file", SchemaType::String()),
+                ("debug", SchemaType::Bool()),
+            ]),
+        );
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "error[missing-token]: 'debug' is not found",
+                "error[type-mismatch]: 'endpoint' has not a integer value 'localhost:3000'",
+                "error[unexpected-submap]: 'log' is not a literal value, is a submap",
+            ]
+        );
+        assert_eq!(result[0].token, Some("debug".to_string()));
+    }
+
+    #[test]
+    fn diagnostics_location() {
+        let sysctl_conf = crate::sysctl::parse_str_with(
+            "endpoint = localhost:3000\nlog.limit =  abc",
+            &crate::sysctl::ParseOptions {
+                origins: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let result = validate_diagnostics(
+            sysctl_conf,
+            SchemaHashMap::from([("log.limit", SchemaType::Integer())]),
+        );
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec!["2:14: error[type-mismatch]: 'log.limit' has not a integer value 'abc'"]
+        );
+        assert_eq!(result[0].length, 3);
+    }
+
+    #[test]
+    fn diagnostics_submaps() {
+        let sysctl_conf = crate::sysctl::parse_str_with(
+            "net.conf.eth0.mtu = 1500\nnet.conf.lo.mtu = big\napp = x",
+            &crate::sysctl::ParseOptions {
+                origins: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let result = validate_diagnostics(
+            sysctl_conf,
+            crate::schema::parse_str("net.conf => map(integer)\napp => object").unwrap(),
+        );
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "3:7: error[unexpected-literal]: 'app' is not a submap, is a literal value",
+                "2:19: error[type-mismatch]: 'net.conf.lo.mtu' has not a integer value 'big'",
+            ]
+        );
+    }
+
+    #[test]
+    fn constraints() {
+        let schema = crate::schema::parse_str(
+            "vm.dirty_ratio => integer
+vm.dirty_background_ratio => integer
+log.enabled => bool
+log.file => optional(string)
+log.syslog => optional(bool)
+constraint vm.dirty_background_ratio < vm.dirty_ratio
+constraint log.enabled == true requires log.file
+constraint log.file conflicts log.syslog",
+        )
+        .unwrap();
+        let sysctl_conf = crate::sysctl::parse_str_with(
+            "vm.dirty_ratio = 10\nvm.dirty_background_ratio = 20\nlog.enabled = true",
+            &crate::sysctl::ParseOptions {
+                origins: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let result = validate_diagnostics(sysctl_conf, schema.clone());
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "2:29: error[constraint-violation]: 'vm.dirty_background_ratio' violates constraint `vm.dirty_background_ratio < vm.dirty_ratio` (20 < 10)",
+                "3:15: error[constraint-violation]: 'log.file' is required by constraint `log.enabled == true requires log.file`",
+            ]
+        );
+
+        let sysctl_conf = crate::sysctl::parse_str(
+            "vm.dirty_ratio = 20\nvm.dirty_background_ratio = 10\nlog.enabled = false\nlog.file = a\nlog.syslog = true",
+        )
+        .unwrap();
+        assert_eq!(
+            validate(sysctl_conf, schema.clone()),
+            Err("'log.syslog' conflicts with 'log.file' by constraint `log.file conflicts log.syslog`".to_string())
+        );
+        // type errors first and the constraints of their tokens are skipped
+        let sysctl_conf = crate::sysctl::parse_str(
+            "vm.dirty_ratio = x\nvm.dirty_background_ratio = 10\nlog.enabled = false",
+        )
+        .unwrap();
+        assert_eq!(
+            validate_diagnostics(sysctl_conf, schema)
+                .iter()
+                .map(|diagnostic| diagnostic.code.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["type-mismatch"]
+        );
+    }
+
+    #[test]
+    fn deprecated() {
+        let schema = crate::schema::parse_str(
+            "log.limit => size
+# @deprecated(use=log.limit)
+log.size => optional(size)
+# @deprecated
+debug => optional(bool)",
+        )
+        .unwrap();
+        let sysctl_conf = crate::sysctl::parse_str_with(
+            "log.limit = 64K\n  log.size = 64K",
+            &crate::sysctl::ParseOptions {
+                origins: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let result = validate_diagnostics(sysctl_conf, schema.clone());
+        assert_eq!(
+            result
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "2:3: warning[deprecated-token]: 'log.size' is deprecated, use 'log.limit' instead"
+            ]
+        );
+        assert_eq!(result[0].length, 8);
+        // warnings never fail validation
+        let sysctl_conf = crate::sysctl::parse_str("log.limit = 64K\nlog.size = 64K").unwrap();
+        assert_eq!(validate(sysctl_conf, schema), Ok(()));
+    }
 }